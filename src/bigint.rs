@@ -0,0 +1,467 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// The most bits `pow` and `factorial` will give, about 40,000 digits, so a
+/// mistyped exponent can't run away with the memory.
+pub const MAX_BITS: u64 = 1 << 17;
+
+/// An arbitrary-precision integer: a sign and a little-endian magnitude in
+/// base 2^32, with no high zero limbs. Zero has an empty magnitude and is
+/// never negative.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BigInt {
+    neg: bool,
+    mag: Vec<u32>,
+}
+
+fn trim(mut mag: Vec<u32>) -> Vec<u32> {
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    mag
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (a, b) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u64;
+    for (i, x) in a.iter().enumerate() {
+        let sum = *x as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+/// `a - b`, where `a >= b`.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, x) in a.iter().enumerate() {
+        let mut diff = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        out.push(diff as u32);
+    }
+    trim(out)
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let t = *x as u64 * *y as u64 + out[i + j] as u64 + carry;
+            out[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    trim(out)
+}
+
+/// Quotient and remainder of a magnitude by a single limb.
+fn divrem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0u32; a.len()];
+    let mut r = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (r << 32) | a[i] as u64;
+        q[i] = (cur / d as u64) as u32;
+        r = cur % d as u64;
+    }
+    (trim(q), r as u32)
+}
+
+/// Shift-and-subtract long division; `b` must be non-zero.
+fn divrem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let (q, r) = divrem_small(a, b[0]);
+        return (q, trim(vec![r]));
+    }
+    let mut q = vec![0u32; a.len()];
+    let mut r: Vec<u32> = vec![];
+    for bit in (0..a.len() * 32).rev() {
+        // r = r << 1 | the next bit of a
+        let mut carry = (a[bit / 32] >> (bit % 32)) & 1;
+        for limb in r.iter_mut() {
+            let next = *limb >> 31;
+            *limb = (*limb << 1) | carry;
+            carry = next;
+        }
+        if carry > 0 {
+            r.push(carry);
+        }
+        if cmp_mag(&r, b) != Ordering::Less {
+            r = sub_mag(&r, b);
+            q[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (trim(q), r)
+}
+
+impl BigInt {
+    fn from_parts(neg: bool, mag: Vec<u32>) -> BigInt {
+        let mag = trim(mag);
+        BigInt {
+            neg: neg && !mag.is_empty(),
+            mag,
+        }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt::from_parts(false, vec![])
+    }
+
+    pub fn from_i64(n: i64) -> BigInt {
+        let m = n.unsigned_abs();
+        BigInt::from_parts(n < 0, vec![m as u32, (m >> 32) as u32])
+    }
+
+    /// The integer `f` holds exactly, or `None` if it has a fractional part
+    /// or isn't finite.
+    pub fn from_f64(f: f64) -> Option<BigInt> {
+        if !f.is_finite() || f.fract() != 0.0 {
+            return None;
+        }
+        if f == 0.0 {
+            return Some(BigInt::zero());
+        }
+        let bits = f.abs().to_bits();
+        let exp = ((bits >> 52) & 0x7ff) as i64 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let m = if exp >= 0 {
+            BigInt::from_i64(mantissa as i64).shl(exp as usize)
+        } else {
+            BigInt::from_i64((mantissa >> -exp) as i64)
+        };
+        Some(if f < 0.0 { -m } else { m })
+    }
+
+    /// Parses an optionally signed string of digits in `radix`.
+    pub fn parse_radix(s: &str, radix: u32) -> Option<BigInt> {
+        let (neg, digits) = match s.strip_prefix('-') {
+            Some(d) => (true, d),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        let mut mag: Vec<u32> = vec![];
+        for c in digits.chars() {
+            let d = c.to_digit(radix)?;
+            mag = add_mag(&mul_mag(&mag, &[radix]), &[d]);
+        }
+        Some(BigInt::from_parts(neg, mag))
+    }
+
+    pub fn parse(s: &str) -> Option<BigInt> {
+        BigInt::parse_radix(s, 10)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.neg
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::from_parts(false, self.mag.clone())
+    }
+
+    /// The nearest float.
+    pub fn to_f64(&self) -> f64 {
+        let m = self
+            .mag
+            .iter()
+            .rev()
+            .fold(0.0, |acc, limb| acc * 4294967296.0 + *limb as f64);
+        if self.neg {
+            -m
+        } else {
+            m
+        }
+    }
+
+    /// The value as a `u32`, if it is one.
+    pub fn to_u32(&self) -> Option<u32> {
+        match (self.neg, self.mag.as_slice()) {
+            (_, []) => Some(0),
+            (false, [x]) => Some(*x),
+            _ => None,
+        }
+    }
+
+    fn shl(&self, bits: usize) -> BigInt {
+        let mut mag = vec![0u32; bits / 32];
+        let shift = bits % 32;
+        let mut carry = 0u32;
+        for limb in &self.mag {
+            if shift == 0 {
+                mag.push(*limb);
+            } else {
+                mag.push((limb << shift) | carry);
+                carry = limb >> (32 - shift);
+            }
+        }
+        mag.push(carry);
+        BigInt::from_parts(self.neg, mag)
+    }
+
+    /// Truncating division, so the remainder takes the sign of `self`, as
+    /// with `/` and `%` on Rust integers.
+    pub fn div_rem(&self, d: &BigInt) -> Result<(BigInt, BigInt), String> {
+        if d.is_zero() {
+            return Err("division by zero".to_owned());
+        }
+        let (q, r) = divrem_mag(&self.mag, &d.mag);
+        Ok((
+            BigInt::from_parts(self.neg != d.neg, q),
+            BigInt::from_parts(self.neg, r),
+        ))
+    }
+
+    /// How many bits the magnitude takes.
+    pub fn bits(&self) -> u64 {
+        match self.mag.last() {
+            Some(top) => self.mag.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    pub fn pow(&self, mut e: u32) -> Result<BigInt, String> {
+        // Only 0, 1 and -1 stay small, whatever the exponent.
+        if self.bits() > 1 && (self.bits() - 1) * e as u64 > MAX_BITS {
+            return Err(format!("pow would be over {MAX_BITS} bits"));
+        }
+        let mut base = self.clone();
+        let mut acc = BigInt::from_i64(1);
+        while e > 0 {
+            if e & 1 == 1 {
+                acc = &acc * &base;
+            }
+            e >>= 1;
+            if e > 0 {
+                base = &base * &base;
+            }
+        }
+        Ok(acc)
+    }
+
+    /// `self^e mod m`, with the result in `0..|m|`.
+    pub fn modpow(&self, e: &BigInt, m: &BigInt) -> Result<BigInt, String> {
+        if e.neg {
+            return Err("modpow needs a non-negative exponent".to_owned());
+        }
+        let m = m.abs();
+        let mut base = self.div_rem(&m)?.1;
+        if base.neg {
+            base = &base + &m;
+        }
+        let mut acc = BigInt::from_i64(1).div_rem(&m)?.1;
+        for i in 0..e.mag.len() * 32 {
+            if (e.mag[i / 32] >> (i % 32)) & 1 == 1 {
+                acc = (&acc * &base).div_rem(&m)?.1;
+            }
+            base = (&base * &base).div_rem(&m)?.1;
+        }
+        Ok(acc)
+    }
+
+    /// Always non-negative.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let r = a.div_rem(&b).unwrap().1;
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    /// Always non-negative.
+    pub fn lcm(&self, other: &BigInt) -> BigInt {
+        if self.is_zero() || other.is_zero() {
+            return BigInt::zero();
+        }
+        (self * other).abs().div_rem(&self.gcd(other)).unwrap().0
+    }
+
+    pub fn factorial(&self) -> Result<BigInt, String> {
+        let n = self
+            .to_u32()
+            .ok_or("factorial needs a small non-negative integer")?;
+        let bits: f64 = (2..=n).map(|i| (i as f64).log2()).sum();
+        if bits > MAX_BITS as f64 {
+            return Err(format!("factorial would be over {MAX_BITS} bits"));
+        }
+        let mut acc = BigInt::from_i64(1);
+        for i in 2..=n {
+            acc = BigInt::from_parts(false, mul_mag(&acc.mag, &[i]));
+        }
+        Ok(acc)
+    }
+
+    /// Digits in `radix` (2 to 36), lower case, with a leading `-` if
+    /// negative.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_owned();
+        }
+        let mut digits = vec![];
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = divrem_small(&mag, radix);
+            digits.push(std::char::from_digit(r, radix).unwrap());
+            mag = q;
+        }
+        if self.neg {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+    fn add(self, rhs: &BigInt) -> BigInt {
+        if self.neg == rhs.neg {
+            return BigInt::from_parts(self.neg, add_mag(&self.mag, &rhs.mag));
+        }
+        match cmp_mag(&self.mag, &rhs.mag) {
+            Ordering::Less => BigInt::from_parts(rhs.neg, sub_mag(&rhs.mag, &self.mag)),
+            _ => BigInt::from_parts(self.neg, sub_mag(&self.mag, &rhs.mag)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+    fn sub(self, rhs: &BigInt) -> BigInt {
+        self + &-rhs.clone()
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+    fn mul(self, rhs: &BigInt) -> BigInt {
+        BigInt::from_parts(self.neg != rhs.neg, mul_mag(&self.mag, &rhs.mag))
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.neg, self.mag)
+    }
+}
+
+/// Decimal digits rather than limbs, so `ps` is readable.
+impl fmt::Debug for BigInt {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.to_string_radix(10))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.to_string_radix(10))
+    }
+}
+
+#[cfg(test)]
+mod bigint_test {
+    use super::*;
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s).unwrap()
+    }
+    #[test]
+    pub fn parse_and_print() {
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(
+            big("123456789012345678901234567890").to_string(),
+            "123456789012345678901234567890"
+        );
+        assert_eq!(big("-255").to_string_radix(16), "-ff");
+        assert_eq!(BigInt::parse_radix("ff", 16), Some(big("255")));
+        assert_eq!(BigInt::parse("12a"), None);
+        assert_eq!(BigInt::parse("-"), None);
+        assert_eq!(
+            BigInt::from_f64(2f64.powi(70)),
+            Some(big("1180591620717411303424"))
+        );
+        assert_eq!(BigInt::from_f64(-3.0), Some(big("-3")));
+        assert_eq!(BigInt::from_f64(0.5), None);
+        assert_eq!(big("-1180591620717411303424").to_f64(), -(2f64.powi(70)));
+    }
+    #[test]
+    pub fn arithmetic() {
+        let a = big("18446744073709551615");
+        assert_eq!(&a + &big("1"), big("18446744073709551616"));
+        assert_eq!(&big("5") - &big("8"), big("-3"));
+        assert_eq!(&big("-5") + &big("8"), big("3"));
+        assert_eq!(&a * &a, big("340282366920938463426481119284349108225"));
+        let (q, r) = big("-340282366920938463426481119284349108226")
+            .div_rem(&a)
+            .unwrap();
+        assert_eq!((q, r), (big("-18446744073709551615"), big("-1")));
+        assert!(big("1").div_rem(&BigInt::zero()).is_err());
+        assert!(big("-2") < big("1"));
+        assert!(big("-20") < big("-3"));
+    }
+    #[test]
+    pub fn number_theory() {
+        assert_eq!(
+            big("2").pow(100).unwrap(),
+            big("1267650600228229401496703205376")
+        );
+        assert_eq!(big("-1").pow(4000000001).unwrap(), big("-1"));
+        assert_eq!(big("2").pow(MAX_BITS as u32).unwrap().bits(), MAX_BITS + 1);
+        assert!(big("2").pow(4000000000).is_err());
+        assert!(big("100000").factorial().is_err());
+        assert_eq!(big("462").gcd(&big("-1071")), big("21"));
+        assert_eq!(big("4").lcm(&big("6")), big("12"));
+        assert_eq!(
+            big("25").factorial().unwrap(),
+            big("15511210043330985984000000")
+        );
+        assert_eq!(
+            big("4").modpow(&big("13"), &big("497")).unwrap(),
+            big("445")
+        );
+        assert_eq!(big("-4").modpow(&big("3"), &big("5")).unwrap(), big("1"));
+        assert!(big("-1").factorial().is_err());
+    }
+}
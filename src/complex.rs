@@ -0,0 +1,289 @@
+use crate::settings;
+use regex::Regex;
+use std::f64::consts::{FRAC_PI_2, LN_10, LN_2};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+const I: Complex = Complex { re: 0.0, im: 1.0 };
+const ONE: Complex = Complex { re: 1.0, im: 0.0 };
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub fn from_polar(r: f64, theta: f64) -> Complex {
+        Complex::new(r * theta.cos(), r * theta.sin())
+    }
+
+    /// Parses `a+bi`, `a-bi`, `bi` or `r∠θ`, with θ in the current angle mode.
+    pub fn parse(s: &str) -> Option<Complex> {
+        if let Some((r, theta)) = s.split_once('∠') {
+            let r = f64::from_str(r).ok()?;
+            let theta = f64::from_str(theta).ok()?;
+            let theta = settings::get().angle.to_radians(theta);
+            return Some(Complex::from_polar(r, theta));
+        }
+        let num = r"(?:\d+\.?\d*|\.\d+)(?:[eE][-+]?\d+)?";
+        let rect = Regex::new(&format!(
+            r"^(?:(?<re>[-+]?{num})(?<im>[-+](?:{num})?)|(?<imonly>[-+]?(?:{num})?))i$"
+        ))
+        .unwrap();
+        let c = rect.captures(s)?;
+        let re = c
+            .name("re")
+            .map(|m| f64::from_str(m.as_str()).unwrap())
+            .unwrap_or(0.0);
+        let im = match c.name("im").or(c.name("imonly")).unwrap().as_str() {
+            "" | "+" => 1.0,
+            "-" => -1.0,
+            m => f64::from_str(m).ok()?,
+        };
+        Some(Complex::new(re, im))
+    }
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn recip(self) -> Complex {
+        ONE / self
+    }
+
+    pub fn exp(self) -> Complex {
+        Complex::from_polar(self.re.exp(), self.im)
+    }
+
+    pub fn ln(self) -> Complex {
+        Complex::new(self.abs().ln(), self.arg())
+    }
+
+    pub fn log10(self) -> Complex {
+        self.ln() / Complex::new(LN_10, 0.0)
+    }
+
+    pub fn log2(self) -> Complex {
+        self.ln() / Complex::new(LN_2, 0.0)
+    }
+
+    pub fn sqrt(self) -> Complex {
+        let r = self.abs();
+        let re = ((r + self.re) / 2.0).sqrt();
+        let im = ((r - self.re) / 2.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    pub fn pow(self, w: Complex) -> Complex {
+        if self.re == 0.0 && self.im == 0.0 {
+            return if w.re == 0.0 && w.im == 0.0 {
+                ONE
+            } else {
+                Complex::new(0.0, 0.0)
+            };
+        }
+        (w * self.ln()).exp()
+    }
+
+    pub fn sin(self) -> Complex {
+        Complex::new(
+            self.re.sin() * self.im.cosh(),
+            self.re.cos() * self.im.sinh(),
+        )
+    }
+
+    pub fn cos(self) -> Complex {
+        Complex::new(
+            self.re.cos() * self.im.cosh(),
+            -self.re.sin() * self.im.sinh(),
+        )
+    }
+
+    pub fn tan(self) -> Complex {
+        self.sin() / self.cos()
+    }
+
+    pub fn sinh(self) -> Complex {
+        Complex::new(
+            self.re.sinh() * self.im.cos(),
+            self.re.cosh() * self.im.sin(),
+        )
+    }
+
+    pub fn cosh(self) -> Complex {
+        Complex::new(
+            self.re.cosh() * self.im.cos(),
+            self.re.sinh() * self.im.sin(),
+        )
+    }
+
+    pub fn tanh(self) -> Complex {
+        self.sinh() / self.cosh()
+    }
+
+    pub fn asin(self) -> Complex {
+        -I * (I * self + (ONE - self * self).sqrt()).ln()
+    }
+
+    pub fn acos(self) -> Complex {
+        Complex::new(FRAC_PI_2, 0.0) - self.asin()
+    }
+
+    pub fn atan(self) -> Complex {
+        I * Complex::new(0.5, 0.0) * ((ONE - I * self).ln() - (ONE + I * self).ln())
+    }
+
+    pub fn asinh(self) -> Complex {
+        (self + (self * self + ONE).sqrt()).ln()
+    }
+
+    pub fn acosh(self) -> Complex {
+        (self + (self + ONE).sqrt() * (self - ONE).sqrt()).ln()
+    }
+
+    pub fn atanh(self) -> Complex {
+        Complex::new(0.5, 0.0) * ((ONE + self).ln() - (ONE - self).ln())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let d = rhs.re * rhs.re + rhs.im * rhs.im;
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+/// Plain decimals, except for very large or small numbers which would
+/// otherwise print a long run of zeros.
+pub fn fmt_float(x: f64) -> String {
+    if x != 0.0 && (x.abs() < 1e-5 || x.abs() >= 1e16) {
+        format!("{x:e}")
+    } else {
+        format!("{x}")
+    }
+}
+
+impl fmt::Display for Complex {
+    /// `a+bi`, or `r∠θ` in polar mode.
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let settings = settings::get();
+        if settings.polar {
+            let theta = settings.angle.radians_in(self.arg());
+            write!(formatter, "{}∠{}", fmt_float(self.abs()), fmt_float(theta))
+        } else if self.im.is_sign_negative() {
+            write!(formatter, "{}-{}i", fmt_float(self.re), fmt_float(-self.im))
+        } else {
+            write!(formatter, "{}+{}i", fmt_float(self.re), fmt_float(self.im))
+        }
+    }
+}
+
+#[cfg(test)]
+mod complex_test {
+    use super::*;
+    fn close(a: Complex, b: Complex) -> bool {
+        (a - b).abs() < 1e-12
+    }
+    #[test]
+    pub fn parse() {
+        assert_eq!(Complex::parse("3+4i"), Some(Complex::new(3.0, 4.0)));
+        assert_eq!(Complex::parse("-2.5e1-i"), Some(Complex::new(-25.0, -1.0)));
+        assert_eq!(Complex::parse("4i"), Some(Complex::new(0.0, 4.0)));
+        assert_eq!(Complex::parse("i"), Some(I));
+        assert!(close(
+            Complex::parse("2∠0").unwrap(),
+            Complex::new(2.0, 0.0)
+        ));
+        assert_eq!(Complex::parse("pi"), None);
+        assert_eq!(Complex::parse("1+i2"), None);
+    }
+    #[test]
+    pub fn arithmetic() {
+        let a = Complex::new(1.0, 2.0);
+        let b = Complex::new(3.0, -1.0);
+        assert_eq!(a * b, Complex::new(5.0, 5.0));
+        assert!(close(a / b * b, a));
+        assert_eq!(a.conj(), Complex::new(1.0, -2.0));
+        assert_eq!(Complex::new(3.0, 4.0).abs(), 5.0);
+    }
+    #[test]
+    pub fn transcendental() {
+        assert!(close(Complex::new(-1.0, 0.0).sqrt(), I));
+        assert!(close(
+            Complex::new(-4.0, 0.0).sqrt(),
+            Complex::new(0.0, 2.0)
+        ));
+        assert!(close(
+            (I * Complex::new(std::f64::consts::PI, 0.0)).exp(),
+            -ONE
+        ));
+        assert!(close(
+            Complex::new(-1.0, 0.0).ln(),
+            Complex::new(0.0, std::f64::consts::PI)
+        ));
+        assert!(close(
+            Complex::new(-8.0, 0.0).pow(Complex::new(1.0 / 3.0, 0.0)),
+            Complex::new(1.0, 3f64.sqrt())
+        ));
+        let z = Complex::new(0.3, -0.7);
+        assert!(close(z.sin().asin(), z));
+        assert!(close(z.cos().acos(), z));
+        assert!(close(z.tan().atan(), z));
+        assert!(close(z.sinh().asinh(), z));
+        assert!(close(z.cosh().acosh(), z));
+        assert!(close(z.tanh().atanh(), z));
+        assert!(close(
+            Complex::new(2.0, 0.0).asin().sin(),
+            Complex::new(2.0, 0.0)
+        ));
+    }
+}
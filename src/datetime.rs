@@ -18,7 +18,7 @@ pub enum Month {
 }
 
 impl Month {
-    pub fn non_leap_year_start_doy_offset(self: &Self) -> u16 {
+    pub fn non_leap_year_start_doy_offset(&self) -> u16 {
         match self {
             Month::Jan => 0,
             Month::Feb => 31,
@@ -35,7 +35,7 @@ impl Month {
         }
     }
 
-    pub fn non_leap_year_days_in_month(self: &Self) -> u8 {
+    pub fn non_leap_year_days_in_month(&self) -> u8 {
         match self {
             Month::Jan => 31,
             Month::Feb => 28,
@@ -82,9 +82,9 @@ impl From<i16> for Month {
     }
 }
 
-impl Into<u16> for &Month {
-    fn into(self: Self) -> u16 {
-        match self {
+impl From<&Month> for u16 {
+    fn from(m: &Month) -> u16 {
+        match m {
             Month::Jan => 0,
             Month::Feb => 1,
             Month::Mar => 2,
@@ -101,9 +101,9 @@ impl Into<u16> for &Month {
     }
 }
 
-impl Into<u16> for Month {
-    fn into(self: Self) -> u16 {
-        match self {
+impl From<Month> for u16 {
+    fn from(m: Month) -> u16 {
+        match m {
             Month::Jan => 0,
             Month::Feb => 1,
             Month::Mar => 2,
@@ -122,7 +122,7 @@ impl Into<u16> for Month {
 
 impl Add<u16> for &Month {
     type Output = Month;
-    fn add(self: Self, rhs: u16) -> Month {
+    fn add(self, rhs: u16) -> Month {
         let x: u16 = self.into();
         Month::from(x + rhs)
     }
@@ -130,22 +130,20 @@ impl Add<u16> for &Month {
 
 impl Add<u16> for Month {
     type Output = Month;
-    fn add(self: Self, rhs: u16) -> Month {
+    fn add(self, rhs: u16) -> Month {
         let x: u16 = self.into();
         Month::from(x + rhs)
     }
 }
 
 impl PartialOrd for Month {
-    fn partial_cmp(self: &Self, other: &Self) -> Option<Ordering> {
-        let lhs: u16 = self.into();
-        let rhs: u16 = other.into();
-        lhs.partial_cmp(&rhs)
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Month {
-    fn cmp(self: &Self, other: &Self) -> Ordering {
+    fn cmp(&self, other: &Self) -> Ordering {
         let lhs: u16 = self.into();
         let rhs: u16 = other.into();
         lhs.cmp(&rhs)
@@ -161,10 +159,10 @@ pub struct Date {
 
 impl Date {
     pub fn new(y: i16, m: Month, d: u8) -> Date {
-        if d < 1 || d > m.non_leap_year_days_in_month() {
-            if !(m == Month::Feb && Date::is_year_leap_year(y) && d > 29) {
-                panic!("{} {:?} {} is not a valid date", y, m, d);
-            }
+        if (d < 1 || d > m.non_leap_year_days_in_month())
+            && !(m == Month::Feb && Date::is_year_leap_year(y) && d > 29)
+        {
+            panic!("{} {:?} {} is not a valid date", y, m, d);
         }
         Date {
             year: y,
@@ -178,14 +176,14 @@ impl Date {
         let cent = year % 100 == 0;
         let fourcent = year % 400 == 0;
 
-        return four && !(cent && !fourcent);
+        four && (!cent || fourcent)
     }
 
-    pub fn is_leap_year(self: &Self) -> bool {
+    pub fn is_leap_year(&self) -> bool {
         Date::is_year_leap_year(self.year)
     }
 
-    pub fn dow_start_of_year(self: &Self) -> DayOfWeek {
+    pub fn dow_start_of_year(&self) -> DayOfWeek {
         let offset_per_year = 365 % 7;
         // There's a better way to do this without a loop, but I don't
         // feel like working it out right now.
@@ -197,11 +195,11 @@ impl Date {
         DayOfWeek::from(1 + ((self.year - 1900) * offset_per_year) + leap_year_count)
     }
 
-    pub fn dow(self: &Self) -> DayOfWeek {
+    pub fn dow(&self) -> DayOfWeek {
         self.dow_start_of_year() + (self.doy() - 1)
     }
 
-    pub fn doy(self: &Self) -> u16 {
+    pub fn doy(&self) -> u16 {
         (self.month.non_leap_year_start_doy_offset() + (self.day_of_month as u16))
             + 1
             + if self.month == Month::Feb && self.is_leap_year() {
@@ -211,7 +209,7 @@ impl Date {
             }
     }
 
-    pub fn days_in_month(self: &Self) -> u8 {
+    pub fn days_in_month(&self) -> u8 {
         self.month.non_leap_year_days_in_month()
             + if self.month == Month::Feb && self.is_leap_year() {
                 1
@@ -220,7 +218,8 @@ impl Date {
             }
     }
 
-    pub fn add_months(self: &Self, i: u16) -> Date {
+    #[allow(dead_code)]
+    pub fn add_months(&self, i: u16) -> Date {
         let m = &self.month + (i % 12);
         let y = self.year + ((i / 12) as i16);
         let mut dom = self.day_of_month;
@@ -232,7 +231,7 @@ impl Date {
         d
     }
 
-    pub fn add_days(self: &Self, i: u16) -> Date {
+    pub fn add_days(&self, i: u16) -> Date {
         let mut i = i;
         let mut d = self.clone();
         while i > 0 {
@@ -289,9 +288,9 @@ impl From<i16> for DayOfWeek {
         DayOfWeek::from(x as u16)
     }
 }
-impl Into<u16> for DayOfWeek {
-    fn into(self: Self) -> u16 {
-        match self {
+impl From<DayOfWeek> for u16 {
+    fn from(d: DayOfWeek) -> u16 {
+        match d {
             DayOfWeek::Sunday => 0,
             DayOfWeek::Monday => 1,
             DayOfWeek::Tuesday => 2,
@@ -302,9 +301,9 @@ impl Into<u16> for DayOfWeek {
         }
     }
 }
-impl Into<u16> for &DayOfWeek {
-    fn into(self: Self) -> u16 {
-        match self {
+impl From<&DayOfWeek> for u16 {
+    fn from(d: &DayOfWeek) -> u16 {
+        match d {
             DayOfWeek::Sunday => 0,
             DayOfWeek::Monday => 1,
             DayOfWeek::Tuesday => 2,
@@ -317,15 +316,13 @@ impl Into<u16> for &DayOfWeek {
 }
 
 impl PartialOrd for DayOfWeek {
-    fn partial_cmp(self: &Self, other: &Self) -> Option<Ordering> {
-        let lhs: u16 = self.into();
-        let rhs: u16 = other.into();
-        lhs.partial_cmp(&rhs)
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for DayOfWeek {
-    fn cmp(self: &Self, other: &Self) -> Ordering {
+    fn cmp(&self, other: &Self) -> Ordering {
         let lhs: u16 = self.into();
         let rhs: u16 = other.into();
         lhs.cmp(&rhs)
@@ -334,7 +331,7 @@ impl Ord for DayOfWeek {
 
 impl Add<u16> for DayOfWeek {
     type Output = DayOfWeek;
-    fn add(self: Self, rhs: u16) -> DayOfWeek {
+    fn add(self, rhs: u16) -> DayOfWeek {
         let x: u16 = self.into();
         DayOfWeek::from(x + rhs)
     }
@@ -345,14 +342,14 @@ mod date_test {
     use super::*;
     #[test]
     pub fn leap_years() {
-        assert_eq!(Date::new(1900, Month::Jan, 1).is_leap_year(), false);
-        assert_eq!(Date::new(1904, Month::Jan, 1).is_leap_year(), true);
-        assert_eq!(Date::new(1908, Month::Jan, 1).is_leap_year(), true);
-        assert_eq!(Date::new(1951, Month::Jan, 1).is_leap_year(), false);
-        assert_eq!(Date::new(1995, Month::Jan, 1).is_leap_year(), false);
-        assert_eq!(Date::new(1996, Month::Jan, 1).is_leap_year(), true);
-        assert_eq!(Date::new(1997, Month::Jan, 1).is_leap_year(), false);
-        assert_eq!(Date::new(2000, Month::Jan, 1).is_leap_year(), true);
+        assert!(!Date::new(1900, Month::Jan, 1).is_leap_year());
+        assert!(Date::new(1904, Month::Jan, 1).is_leap_year());
+        assert!(Date::new(1908, Month::Jan, 1).is_leap_year());
+        assert!(!Date::new(1951, Month::Jan, 1).is_leap_year());
+        assert!(!Date::new(1995, Month::Jan, 1).is_leap_year());
+        assert!(Date::new(1996, Month::Jan, 1).is_leap_year());
+        assert!(!Date::new(1997, Month::Jan, 1).is_leap_year());
+        assert!(Date::new(2000, Month::Jan, 1).is_leap_year());
     }
     #[test]
     pub fn dow() {
@@ -0,0 +1,258 @@
+use crate::settings::Rounding;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// How many places a quotient gets when no fixed scale is set.
+pub const DIV_PLACES: u32 = 16;
+
+/// The most places a product can have, so a power of a small number can't
+/// fill the screen with zeros.
+const MAX_SCALE: u32 = 1000;
+
+/// A base-10 number, `coef / 10^scale`, so `0.1` is held exactly.
+///
+/// Sums, differences and products are exact, with the scale growing as
+/// needed; the arithmetic is checked and gives `None` on overflow.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    coef: i128,
+    scale: u32,
+}
+
+fn pow10(n: u32) -> Option<i128> {
+    10i128.checked_pow(n)
+}
+
+/// `n / d` rounded to an integer by `mode`.
+fn round_div(n: i128, d: i128, mode: Rounding) -> i128 {
+    let q = n / d;
+    let r = n % d;
+    if r == 0 || mode == Rounding::Truncate {
+        return q;
+    }
+    let away = q + if (n < 0) == (d < 0) { 1 } else { -1 };
+    match (2 * r.unsigned_abs()).cmp(&d.unsigned_abs()) {
+        Ordering::Less => q,
+        Ordering::Greater => away,
+        Ordering::Equal if mode == Rounding::HalfUp || q % 2 != 0 => away,
+        Ordering::Equal => q,
+    }
+}
+
+impl Decimal {
+    pub fn new(coef: i128, scale: u32) -> Decimal {
+        Decimal { coef, scale }
+    }
+
+    /// Parses `12.34`, `-5` or `.5`.
+    pub fn parse(s: &str) -> Option<Decimal> {
+        let re = Regex::new(r"^(?<sign>[-+]?)(?<int>\d*)(?:\.(?<frac>\d*))?$").unwrap();
+        let c = re.captures(s)?;
+        let int = &c["int"];
+        let frac = c.name("frac").map(|m| m.as_str()).unwrap_or("");
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+        let coef = i128::from_str(&format!("{int}{frac}0")).ok()? / 10;
+        let coef = if &c["sign"] == "-" { -coef } else { coef };
+        Some(Decimal::new(coef, frac.len() as u32))
+    }
+
+    /// The shortest decimal that converts back to exactly `x`, so `0.1`
+    /// gives `0.1`.
+    pub fn from_f64(x: f64) -> Option<Decimal> {
+        if !x.is_finite() {
+            return None;
+        }
+        Decimal::parse(&format!("{x}"))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        f64::from_str(&self.to_string()).unwrap()
+    }
+
+    /// The same value with `scale` places, if that's no fewer than it has.
+    fn rescale(self, scale: u32) -> Option<Decimal> {
+        let coef = self
+            .coef
+            .checked_mul(pow10(scale.checked_sub(self.scale)?)?)?;
+        Some(Decimal::new(coef, scale))
+    }
+
+    /// Both at the larger of their two scales.
+    fn align(self, rhs: Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(rhs.scale);
+        Some((self.rescale(scale)?.coef, rhs.rescale(scale)?.coef, scale))
+    }
+
+    /// Rounded to at most `places` places.
+    pub fn round(self, places: u32, mode: Rounding) -> Decimal {
+        if places >= self.scale {
+            return self;
+        }
+        match pow10(self.scale - places) {
+            Some(p) => Decimal::new(round_div(self.coef, p, mode), places),
+            // More places than an i128 has digits: the value rounds to zero.
+            None => Decimal::new(0, places),
+        }
+    }
+
+    /// Without trailing zeros after the point.
+    pub fn trimmed(self) -> Decimal {
+        let mut d = self;
+        while d.scale > 0 && d.coef % 10 == 0 {
+            d = Decimal::new(d.coef / 10, d.scale - 1);
+        }
+        d
+    }
+
+    pub fn checked_add(self, rhs: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.align(rhs)?;
+        Some(Decimal::new(a.checked_add(b)?, scale))
+    }
+
+    pub fn checked_sub(self, rhs: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.align(rhs)?;
+        Some(Decimal::new(a.checked_sub(b)?, scale))
+    }
+
+    pub fn checked_mul(self, rhs: Decimal) -> Option<Decimal> {
+        let scale = Some(self.scale + rhs.scale).filter(|s| *s <= MAX_SCALE)?;
+        Some(Decimal::new(self.coef.checked_mul(rhs.coef)?, scale))
+    }
+
+    /// The quotient rounded to `places` places; `None` for division by zero
+    /// as well as overflow.
+    pub fn checked_div(self, rhs: Decimal, places: u32, mode: Rounding) -> Option<Decimal> {
+        if rhs.coef == 0 {
+            return None;
+        }
+        // coef = a/10^sa / (b/10^sb) * 10^places
+        let shift = (rhs.scale + places) as i64 - self.scale as i64;
+        let (n, d) = if shift >= 0 {
+            (self.coef.checked_mul(pow10(shift as u32)?)?, rhs.coef)
+        } else {
+            (self.coef, rhs.coef.checked_mul(pow10((-shift) as u32)?)?)
+        };
+        Some(Decimal::new(round_div(n, d, mode), places))
+    }
+
+    /// By repeated squaring, giving up at the first product that overflows.
+    pub fn checked_pow(self, mut e: u32) -> Option<Decimal> {
+        let mut base = self;
+        let mut acc = Decimal::new(1, 0);
+        while e > 0 {
+            if e & 1 == 1 {
+                acc = acc.checked_mul(base)?;
+            }
+            e >>= 1;
+            if e > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Some(acc)
+    }
+
+    /// `self / 100`, which is always exact.
+    pub fn percent(self) -> Decimal {
+        Decimal::new(self.coef, self.scale + 2)
+    }
+
+    pub fn abs(self) -> Decimal {
+        Decimal::new(self.coef.abs(), self.scale)
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.trimmed().scale == 0
+    }
+}
+
+/// By value, so `1.50` equals `1.5`.
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        match self.align(*other) {
+            Some((a, b, _)) => a.cmp(&b),
+            None => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = format!("{:0>1$}", self.coef.unsigned_abs(), self.scale as usize + 1);
+        let (int, frac) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.coef < 0 { "-" } else { "" };
+        if frac.is_empty() {
+            write!(formatter, "{sign}{int}")
+        } else {
+            write!(formatter, "{sign}{int}.{frac}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod decimal_test {
+    use super::*;
+    fn d(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap()
+    }
+    #[test]
+    pub fn parse_and_print() {
+        assert_eq!(d("12.34").to_string(), "12.34");
+        assert_eq!(d("-0.05").to_string(), "-0.05");
+        assert_eq!(d(".5").to_string(), "0.5");
+        assert_eq!(d("7.").to_string(), "7");
+        assert_eq!(Decimal::parse("."), None);
+        assert_eq!(Decimal::parse("1e5"), None);
+        assert_eq!(Decimal::from_f64(0.1), Some(d("0.1")));
+        assert_eq!(d("-2.50").to_f64(), -2.5);
+    }
+    #[test]
+    pub fn arithmetic() {
+        assert_eq!(d("0.1").checked_add(d("0.2")), Some(d("0.3")));
+        assert_eq!(d("1").checked_sub(d("0.01")), Some(d("0.99")));
+        assert_eq!(d("12.34").checked_mul(d("1.5")), Some(d("18.510")));
+        assert_eq!(
+            d("1").checked_div(d("3"), 4, Rounding::HalfEven),
+            Some(d("0.3333"))
+        );
+        assert_eq!(d("1").checked_div(d("0"), 4, Rounding::HalfEven), None);
+        assert_eq!(d("1.05").checked_pow(2), Some(d("1.1025")));
+        assert_eq!(d("1.05").checked_pow(0), Some(d("1")));
+        assert_eq!(d("-1").checked_pow(2000000001), Some(d("-1")));
+        assert_eq!(d("2").checked_pow(2000000000), None);
+        assert_eq!(d("0.1").checked_pow(2000000000), None);
+        assert!(d("0.3") > d("0.25"));
+        assert_eq!(d("1.50").cmp(&d("1.5")), Ordering::Equal);
+        assert_eq!(d("1.2300").trimmed().to_string(), "1.23");
+        assert_eq!(d("12.5").percent().to_string(), "0.125");
+    }
+    #[test]
+    pub fn rounding() {
+        let r = |s, mode| d(s).round(0, mode).to_string();
+        assert_eq!(r("2.5", Rounding::HalfEven), "2");
+        assert_eq!(r("3.5", Rounding::HalfEven), "4");
+        assert_eq!(r("2.5", Rounding::HalfUp), "3");
+        assert_eq!(r("-2.5", Rounding::HalfUp), "-3");
+        assert_eq!(r("2.9", Rounding::Truncate), "2");
+        assert_eq!(r("-2.9", Rounding::Truncate), "-2");
+        assert_eq!(r("2.51", Rounding::HalfEven), "3");
+        assert_eq!(d("1.005").round(2, Rounding::HalfUp).to_string(), "1.01");
+    }
+}
@@ -0,0 +1,304 @@
+const BASE64_STD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+fn encode_base64(input: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut out = String::new();
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(alphabet[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else if pad {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(input: &str, alphabet: &[u8; 64]) -> Result<Vec<u8>, String> {
+    let input = input.trim_end_matches('=');
+    let mut out = vec![];
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in input.chars() {
+        let v = alphabet
+            .iter()
+            .position(|a| *a as char == c)
+            .ok_or(format!("invalid base64 character {c:?}"))?;
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if bits >= 6 {
+        return Err("truncated base64 input".to_owned());
+    }
+    Ok(out)
+}
+
+pub fn base64_encode(input: &str) -> String {
+    encode_base64(input.as_bytes(), BASE64_STD, true)
+}
+
+pub fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
+    decode_base64(input, BASE64_STD)
+}
+
+/// URL-safe base64 is written without padding, as it is in JWTs.
+pub fn base64url_encode(input: &str) -> String {
+    encode_base64(input.as_bytes(), BASE64_URL, false)
+}
+
+pub fn base64url_decode(input: &str) -> Result<Vec<u8>, String> {
+    decode_base64(input, BASE64_URL)
+}
+
+pub fn base32_encode(input: &str) -> String {
+    let mut out = String::new();
+    for chunk in input.as_bytes().chunks(5) {
+        let mut n: u64 = 0;
+        for i in 0..5 {
+            n = (n << 8) | *chunk.get(i).unwrap_or(&0) as u64;
+        }
+        let used = (chunk.len() * 8).div_ceil(5);
+        for i in 0..8 {
+            if i < used {
+                out.push(BASE32[((n >> (35 - 5 * i)) & 0x1f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn base32_decode(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.trim_end_matches('=');
+    // No whole number of bytes leaves these many characters in the last group.
+    if [1, 3, 6].contains(&(input.chars().count() % 8)) {
+        return Err("truncated base32 input".to_owned());
+    }
+    let mut out = vec![];
+    let mut acc: u64 = 0;
+    let mut bits = 0;
+    for c in input.chars() {
+        let v = BASE32
+            .iter()
+            .position(|a| *a as char == c.to_ascii_uppercase())
+            .ok_or(format!("invalid base32 character {c:?}"))?;
+        acc = (acc << 5) | v as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+pub fn hex_encode(input: &str) -> String {
+    let mut out = String::new();
+    for b in input.bytes() {
+        out.push(HEX[(b >> 4) as usize] as char);
+        out.push(HEX[(b & 0xf) as usize] as char);
+    }
+    out
+}
+
+fn hex_digit(c: u8) -> Result<u8, String> {
+    (c as char)
+        .to_digit(16)
+        .map(|d| d as u8)
+        .ok_or(format!("invalid hex digit {:?}", c as char))
+}
+
+pub fn hex_decode(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.strip_prefix("0x").unwrap_or(input).as_bytes();
+    if !input.len().is_multiple_of(2) {
+        return Err("hex input must have an even number of digits".to_owned());
+    }
+    let mut out = vec![];
+    for pair in input.chunks(2) {
+        out.push((hex_digit(pair[0])? << 4) | hex_digit(pair[1])?);
+    }
+    Ok(out)
+}
+
+fn percent_encode_with(input: &str, form: bool) -> String {
+    let mut out = String::new();
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            b' ' if form => out.push('+'),
+            _ => {
+                out.push('%');
+                out.push(HEX[(b >> 4) as usize] as char);
+                out.push(HEX[(b & 0xf) as usize] as char);
+            }
+        }
+    }
+    out
+}
+
+fn percent_decode_with(input: &str, form: bool) -> Result<Vec<u8>, String> {
+    let bytes = input.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                if i + 2 >= bytes.len() {
+                    return Err("truncated percent escape".to_owned());
+                }
+                out.push((hex_digit(bytes[i + 1])? << 4) | hex_digit(bytes[i + 2])?);
+                i += 3;
+            }
+            b'+' if form => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// RFC 3986 percent-encoding; everything but the unreserved set is escaped.
+pub fn percent_encode(input: &str) -> String {
+    percent_encode_with(input, false)
+}
+
+pub fn percent_decode(input: &str) -> Result<Vec<u8>, String> {
+    percent_decode_with(input, false)
+}
+
+/// Form/query-string encoding, which is percent-encoding with spaces as `+`.
+pub fn url_encode(input: &str) -> String {
+    percent_encode_with(input, true)
+}
+
+pub fn url_decode(input: &str) -> Result<Vec<u8>, String> {
+    percent_decode_with(input, true)
+}
+
+/// RFC 2045 quoted-printable, with soft line breaks every 76 characters.
+pub fn qp_encode(input: &str) -> String {
+    let mut out = String::new();
+    let mut line_len = 0;
+    let bytes = input.as_bytes();
+    for (i, b) in bytes.iter().enumerate() {
+        if *b == b'\n' {
+            out.push('\n');
+            line_len = 0;
+            continue;
+        }
+        let trailing_ws =
+            (*b == b' ' || *b == b'\t') && (i + 1 == bytes.len() || bytes[i + 1] == b'\n');
+        let literal =
+            (*b == b' ' || *b == b'\t' || (33..=126).contains(b)) && *b != b'=' && !trailing_ws;
+        let piece = if literal {
+            (*b as char).to_string()
+        } else {
+            format!("={:02X}", b)
+        };
+        if line_len + piece.len() > 75 {
+            out.push_str("=\n");
+            line_len = 0;
+        }
+        line_len += piece.len();
+        out.push_str(&piece);
+    }
+    out
+}
+
+pub fn qp_decode(input: &str) -> Result<Vec<u8>, String> {
+    let bytes = input.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'=' {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        match bytes.get(i + 1) {
+            Some(b'\n') => i += 2,
+            Some(b'\r') if bytes.get(i + 2) == Some(&b'\n') => i += 3,
+            Some(h) if i + 2 < bytes.len() => {
+                out.push((hex_digit(*h)? << 4) | hex_digit(bytes[i + 2])?);
+                i += 3;
+            }
+            _ => return Err("truncated quoted-printable escape".to_owned()),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod encoding_test {
+    use super::*;
+    #[test]
+    pub fn base64() {
+        assert_eq!(base64_encode("Man"), "TWFu");
+        assert_eq!(base64_encode("Ma"), "TWE=");
+        assert_eq!(base64_encode("M"), "TQ==");
+        assert_eq!(base64_decode("TWE=").unwrap(), b"Ma");
+        assert_eq!(base64_decode("TQ").unwrap(), b"M");
+        assert!(base64_decode("T*==").is_err());
+        assert_eq!(base64url_encode("??>"), "Pz8-");
+        assert_eq!(
+            base64url_decode("eyJhbGciOiJIUzI1NiJ9").unwrap(),
+            b"{\"alg\":\"HS256\"}"
+        );
+    }
+    #[test]
+    pub fn base32() {
+        assert_eq!(base32_encode("foobar"), "MZXW6YTBOI======");
+        assert_eq!(base32_decode("MZXW6YTBOI======").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw6").unwrap(), b"foo");
+        assert!(base32_decode("M").is_err());
+        assert!(base32_decode("MZX").is_err());
+        assert!(base32_decode("MZXW6Y==").is_err());
+    }
+    #[test]
+    pub fn hex() {
+        assert_eq!(hex_encode("hi!"), "686921");
+        assert_eq!(hex_decode("686921").unwrap(), b"hi!");
+        // Bytes that aren't text still decode.
+        assert_eq!(hex_decode("00ff").unwrap(), [0, 255]);
+        assert!(hex_decode("68692").is_err());
+        assert!(hex_decode("zz").is_err());
+    }
+    #[test]
+    pub fn percent() {
+        assert_eq!(percent_encode("a b/c"), "a%20b%2Fc");
+        assert_eq!(url_encode("a b/c"), "a+b%2Fc");
+        assert_eq!(percent_decode("a%20b%2Fc").unwrap(), b"a b/c");
+        assert_eq!(url_decode("a+b%2fc").unwrap(), b"a b/c");
+        assert!(percent_decode("a%2").is_err());
+    }
+    #[test]
+    pub fn quoted_printable() {
+        assert_eq!(qp_encode("a=b \nc"), "a=3Db=20\nc");
+        assert_eq!(qp_decode("a=3Db=20\nc").unwrap(), b"a=b \nc");
+        assert_eq!(qp_decode("ab=\ncd").unwrap(), b"abcd");
+        assert!(qp_decode("a=3").is_err());
+    }
+}
@@ -0,0 +1,488 @@
+use crate::complex::fmt_float;
+use regex::Regex;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::fmt;
+use std::str::FromStr;
+
+/// A closed range of floats known to hold the true value.
+///
+/// Every operation rounds outward, so the result always contains the exact
+/// answer for every point in its arguments. Exact results of arithmetic,
+/// including integer powers, are left as they are, which is checked with
+/// error-free transformations; results of transcendental functions and
+/// fractional powers are widened by an ulp either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+/// The rounding error of `a + b`, so `a + b == s + err` exactly.
+fn two_sum(a: f64, b: f64, s: f64) -> f64 {
+    let bb = s - a;
+    (a - (s - bb)) + (b - bb)
+}
+
+/// Moves `x` down an ulp if the exact value it approximates is below it,
+/// as told by the sign of `err` (exact minus rounded).
+fn down(x: f64, err: f64) -> f64 {
+    if err < 0.0 || err.is_nan() {
+        x.next_down()
+    } else {
+        x
+    }
+}
+
+fn up(x: f64, err: f64) -> f64 {
+    if err > 0.0 || err.is_nan() {
+        x.next_up()
+    } else {
+        x
+    }
+}
+
+fn add_down(a: f64, b: f64) -> f64 {
+    let s = a + b;
+    down(s, two_sum(a, b, s))
+}
+
+fn add_up(a: f64, b: f64) -> f64 {
+    let s = a + b;
+    up(s, two_sum(a, b, s))
+}
+
+fn mul_down(a: f64, b: f64) -> f64 {
+    let p = a * b;
+    down(p, a.mul_add(b, -p))
+}
+
+fn mul_up(a: f64, b: f64) -> f64 {
+    let p = a * b;
+    up(p, a.mul_add(b, -p))
+}
+
+/// `x^n` for `x >= 0` by repeated squaring, each product rounded the way
+/// `mul` rounds it.
+fn powi_by(x: f64, n: u32, mul: fn(f64, f64) -> f64) -> f64 {
+    let (mut base, mut n, mut r) = (x, n, 1.0);
+    while n > 0 {
+        if n & 1 == 1 {
+            r = mul(r, base);
+        }
+        n >>= 1;
+        if n > 0 {
+            base = mul(base, base);
+        }
+    }
+    r
+}
+
+/// A lower bound on `x^n` for odd `n`, which keeps the sign of `x`.
+fn odd_powi_down(x: f64, n: u32) -> f64 {
+    if x < 0.0 {
+        -powi_by(-x, n, mul_up)
+    } else {
+        powi_by(x, n, mul_down)
+    }
+}
+
+fn odd_powi_up(x: f64, n: u32) -> f64 {
+    -odd_powi_down(-x, n)
+}
+
+/// The error of `q = a / b` has the sign of the remainder `a - q*b` over
+/// `b`.
+fn div_down(a: f64, b: f64) -> f64 {
+    let q = a / b;
+    down(q, (-q).mul_add(b, a) * b.signum())
+}
+
+fn div_up(a: f64, b: f64) -> f64 {
+    let q = a / b;
+    up(q, (-q).mul_add(b, a) * b.signum())
+}
+
+/// Whether the decimal `text` parsed to exactly `x`, by comparing it with
+/// every digit of `x`. Exponents are taken as inexact.
+fn is_exact(text: &str, x: f64) -> bool {
+    let norm = |s: &str| {
+        let s = if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            s
+        };
+        s.trim_start_matches('0').to_owned()
+    };
+    !text.contains(['e', 'E'])
+        && norm(text.trim_start_matches(['-', '+'])) == norm(&format!("{:.1100}", x.abs()))
+}
+
+/// Whether `x + k*period` lies in `[lo, hi]` for some integer k.
+fn hits(lo: f64, hi: f64, x: f64, period: f64) -> bool {
+    let k = ((lo - x) / period).ceil();
+    x + k * period <= hi
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Interval {
+        Interval { lo, hi }
+    }
+
+    pub fn point(x: f64) -> Interval {
+        Interval::new(x, x)
+    }
+
+    /// `lo..hi`, or an error if they're the wrong way round or NaN.
+    pub fn checked(lo: f64, hi: f64) -> Result<Interval, String> {
+        if lo <= hi {
+            Ok(Interval::new(lo, hi))
+        } else {
+            Err(format!("[{lo},{hi}] is not an interval"))
+        }
+    }
+
+    /// `x ± e`.
+    pub fn around(x: f64, e: f64) -> Result<Interval, String> {
+        let e = e.abs();
+        Interval::checked(add_down(x, -e), add_up(x, e))
+    }
+
+    /// Parses `[lo,hi]`, `x±e` or `x+-e`. Numbers that aren't exact as
+    /// floats, such as `0.1`, are taken as the ulp either side of the
+    /// nearest float.
+    pub fn parse(s: &str) -> Option<Interval> {
+        let num = r"[-+]?(?:\d+\.?\d*|\.\d+)(?:[eE][-+]?\d+)?";
+        let re = Regex::new(&format!(
+            r"^(?:\[(?<lo>{num}),(?<hi>{num})\]|(?<x>{num})(?:±|\+-)(?<e>{num}))$"
+        ))
+        .unwrap();
+        let c = re.captures(s)?;
+        let f = |name| {
+            let text = &c[name];
+            let x = f64::from_str(text).unwrap();
+            if !is_exact(text, x) {
+                Interval::widen(x, x)
+            } else {
+                Interval::point(x)
+            }
+        };
+        if c.name("lo").is_some() {
+            Interval::checked(f("lo").lo, f("hi").hi).ok()
+        } else {
+            let (x, e) = (f("x"), f("e").abs());
+            Interval::checked(add_down(x.lo, -e.hi), add_up(x.hi, e.hi)).ok()
+        }
+    }
+
+    /// Widened by an ulp each side, for results of functions that aren't
+    /// correctly rounded.
+    fn widen(lo: f64, hi: f64) -> Interval {
+        Interval::new(lo.next_down(), hi.next_up())
+    }
+
+    pub fn width(self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn mid(self) -> f64 {
+        self.lo + (self.hi - self.lo) / 2.0
+    }
+
+    pub fn contains(self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn add(self, rhs: Interval) -> Interval {
+        Interval::new(add_down(self.lo, rhs.lo), add_up(self.hi, rhs.hi))
+    }
+
+    pub fn sub(self, rhs: Interval) -> Interval {
+        Interval::new(add_down(self.lo, -rhs.hi), add_up(self.hi, -rhs.lo))
+    }
+
+    pub fn neg(self) -> Interval {
+        Interval::new(-self.hi, -self.lo)
+    }
+
+    pub fn mul(self, rhs: Interval) -> Interval {
+        let pairs = [
+            (self.lo, rhs.lo),
+            (self.lo, rhs.hi),
+            (self.hi, rhs.lo),
+            (self.hi, rhs.hi),
+        ];
+        Interval::new(
+            pairs
+                .iter()
+                .map(|(a, b)| mul_down(*a, *b))
+                .fold(f64::INFINITY, f64::min),
+            pairs
+                .iter()
+                .map(|(a, b)| mul_up(*a, *b))
+                .fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+
+    pub fn div(self, rhs: Interval) -> Result<Interval, String> {
+        if rhs.contains(0.0) {
+            return Err("division by an interval containing zero".to_owned());
+        }
+        let pairs = [
+            (self.lo, rhs.lo),
+            (self.lo, rhs.hi),
+            (self.hi, rhs.lo),
+            (self.hi, rhs.hi),
+        ];
+        Ok(Interval::new(
+            pairs
+                .iter()
+                .map(|(a, b)| div_down(*a, *b))
+                .fold(f64::INFINITY, f64::min),
+            pairs
+                .iter()
+                .map(|(a, b)| div_up(*a, *b))
+                .fold(f64::NEG_INFINITY, f64::max),
+        ))
+    }
+
+    pub fn recip(self) -> Result<Interval, String> {
+        Interval::point(1.0).div(self)
+    }
+
+    pub fn abs(self) -> Interval {
+        if self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            self.neg()
+        } else {
+            Interval::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+
+    pub fn sq(self) -> Interval {
+        let a = self.abs();
+        Interval::new(mul_down(a.lo, a.lo), mul_up(a.hi, a.hi))
+    }
+
+    /// Applies a function that rises across the whole interval, which must
+    /// lie within `domain`.
+    pub fn increasing(
+        self,
+        f: fn(f64) -> f64,
+        domain: (f64, f64),
+        name: &str,
+    ) -> Result<Interval, String> {
+        if self.lo < domain.0 || self.hi > domain.1 {
+            return Err(format!("{self} is outside the domain of {name}"));
+        }
+        Ok(Interval::widen(f(self.lo), f(self.hi)))
+    }
+
+    /// As [`Interval::increasing`], for a function that falls.
+    pub fn decreasing(
+        self,
+        f: fn(f64) -> f64,
+        domain: (f64, f64),
+        name: &str,
+    ) -> Result<Interval, String> {
+        if self.lo < domain.0 || self.hi > domain.1 {
+            return Err(format!("{self} is outside the domain of {name}"));
+        }
+        Ok(Interval::widen(f(self.hi), f(self.lo)))
+    }
+
+    pub fn sqrt(self) -> Result<Interval, String> {
+        if self.lo < 0.0 {
+            return Err(format!("{self} is outside the domain of sqrt"));
+        }
+        // The exact root minus the rounded one has the sign of x - r*r.
+        let err = |x: f64, r: f64| -r.mul_add(r, -x);
+        let (lo, hi) = (self.lo.sqrt(), self.hi.sqrt());
+        Ok(Interval::new(
+            down(lo, err(self.lo, lo)),
+            up(hi, err(self.hi, hi)),
+        ))
+    }
+
+    pub fn cosh(self) -> Interval {
+        let a = self.abs();
+        let r = Interval::widen(a.lo.cosh(), a.hi.cosh());
+        Interval::new(r.lo.max(1.0), r.hi)
+    }
+
+    /// A sine-like function of period 2π with its peaks at `peak` and its
+    /// troughs half a period on: the extremes are at the ends unless a peak
+    /// or trough falls inside.
+    fn periodic(self, f: fn(f64) -> f64, peak: f64) -> Interval {
+        if self.width() >= TAU {
+            return Interval::new(-1.0, 1.0);
+        }
+        let (a, b) = (f(self.lo), f(self.hi));
+        let lo = if hits(self.lo, self.hi, peak + PI, TAU) {
+            -1.0
+        } else {
+            a.min(b).next_down().max(-1.0)
+        };
+        let hi = if hits(self.lo, self.hi, peak, TAU) {
+            1.0
+        } else {
+            a.max(b).next_up().min(1.0)
+        };
+        Interval::new(lo, hi)
+    }
+
+    /// In radians, as are `cos` and `tan`.
+    pub fn sin(self) -> Interval {
+        self.periodic(f64::sin, FRAC_PI_2)
+    }
+
+    pub fn cos(self) -> Interval {
+        self.periodic(f64::cos, 0.0)
+    }
+
+    pub fn tan(self) -> Result<Interval, String> {
+        if self.width() >= PI || hits(self.lo, self.hi, FRAC_PI_2, PI) {
+            return Err(format!("{self} contains a pole of tan"));
+        }
+        Ok(Interval::widen(self.lo.tan(), self.hi.tan()))
+    }
+
+    /// The angle of the points `(x, y)` with `y` in `self`, in radians. It
+    /// takes its extremes at the corners, unless the box contains the origin
+    /// or straddles the cut along the negative x axis, where it's all angles.
+    pub fn atan2(self, x: Interval) -> Interval {
+        let y = self;
+        if (x.lo < 0.0 && y.lo < 0.0 && y.hi >= 0.0) || (x.contains(0.0) && y.contains(0.0)) {
+            return Interval::widen(-PI, PI);
+        }
+        let corners = [
+            y.lo.atan2(x.lo),
+            y.lo.atan2(x.hi),
+            y.hi.atan2(x.lo),
+            y.hi.atan2(x.hi),
+        ];
+        let lo = corners.iter().copied().fold(f64::INFINITY, f64::min);
+        let hi = corners.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Interval::widen(lo, hi)
+    }
+
+    /// Integer powers follow the sign rules; other powers need a positive
+    /// base.
+    pub fn powf(self, e: f64) -> Result<Interval, String> {
+        if e.fract() == 0.0 && e.abs() < i32::MAX as f64 {
+            let n = e as i32;
+            let base = if n < 0 { self.recip()? } else { self };
+            let n = n.unsigned_abs();
+            return Ok(if n.is_multiple_of(2) {
+                let a = base.abs();
+                Interval::new(powi_by(a.lo, n, mul_down), powi_by(a.hi, n, mul_up))
+            } else {
+                Interval::new(odd_powi_down(base.lo, n), odd_powi_up(base.hi, n))
+            });
+        }
+        if self.lo < 0.0 {
+            return Err(format!("{self} to a fractional power"));
+        }
+        let (a, b) = (self.lo.powf(e), self.hi.powf(e));
+        Ok(Interval::widen(a.min(b), a.max(b)))
+    }
+
+    /// Both the base and the exponent may be intervals, for a positive
+    /// base.
+    pub fn pow(self, e: Interval) -> Result<Interval, String> {
+        if e.lo == e.hi {
+            return self.powf(e.lo);
+        }
+        if self.lo <= 0.0 {
+            return Err(format!("{self} to an interval power"));
+        }
+        let ln = self.increasing(f64::ln, (0.0, f64::INFINITY), "ln")?;
+        ln.mul(e)
+            .increasing(f64::exp, (f64::NEG_INFINITY, f64::INFINITY), "exp")
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "[{},{}]", fmt_float(self.lo), fmt_float(self.hi))
+    }
+}
+
+#[cfg(test)]
+mod interval_test {
+    use super::*;
+    fn iv(lo: f64, hi: f64) -> Interval {
+        Interval::new(lo, hi)
+    }
+    #[test]
+    pub fn parse() {
+        assert_eq!(Interval::parse("[1,2]"), Some(iv(1.0, 2.0)));
+        let i = Interval::parse("[-1e3,-2.5]").unwrap();
+        assert!(i.contains(-1000.0) && i.contains(-2.5) && i.lo < -1000.0);
+        assert_eq!(Interval::parse("[2,1]"), None);
+        assert_eq!(Interval::parse("10±2"), Some(iv(8.0, 12.0)));
+        assert_eq!(Interval::parse("10+-2"), Some(iv(8.0, 12.0)));
+        assert_eq!(Interval::parse("[0.5,2.25]"), Some(iv(0.5, 2.25)));
+        assert!(!is_exact("0.1", 0.1));
+        assert!(is_exact("-007.50", -7.5));
+        let i = Interval::parse("1±0.1").unwrap();
+        assert!(i.lo < 0.9 && i.hi >= 1.1);
+    }
+    #[test]
+    pub fn arithmetic() {
+        assert_eq!(iv(1.0, 2.0).add(iv(3.0, 4.0)), iv(4.0, 6.0));
+        assert_eq!(iv(1.0, 2.0).sub(iv(3.0, 4.0)), iv(-3.0, -1.0));
+        assert_eq!(iv(-1.0, 2.0).mul(iv(3.0, 4.0)), iv(-4.0, 8.0));
+        assert_eq!(iv(1.0, 2.0).div(iv(4.0, 8.0)), Ok(iv(0.125, 0.5)));
+        assert!(iv(1.0, 2.0).div(iv(-1.0, 1.0)).is_err());
+        let third = iv(1.0, 1.0).div(iv(3.0, 3.0)).unwrap();
+        assert!(third.lo < third.hi);
+        assert!(third.contains(1.0 / 3.0));
+        let sum = iv(0.1, 0.1).add(iv(0.2, 0.2));
+        assert!(sum.lo < sum.hi && sum.contains(0.30000000000000004));
+        assert_eq!(iv(-3.0, 2.0).sq(), iv(0.0, 9.0));
+        assert_eq!(iv(4.0, 9.0).sqrt(), Ok(iv(2.0, 3.0)));
+        assert!(iv(-1.0, 4.0).sqrt().is_err());
+    }
+    #[test]
+    pub fn functions() {
+        let s = iv(0.0, PI).sin();
+        assert_eq!(s.hi, 1.0);
+        assert!(s.lo <= 0.0 && s.lo > -1e-15);
+        assert_eq!(iv(3.0, 3.5).cos().lo, -1.0);
+        assert_eq!(iv(-10.0, 10.0).cos(), iv(-1.0, 1.0));
+        assert!(iv(1.0, 2.0).tan().is_err());
+        let e = iv(0.0, 1.0)
+            .increasing(f64::exp, (f64::NEG_INFINITY, f64::INFINITY), "exp")
+            .unwrap();
+        assert!(e.contains(1.0) && e.contains(std::f64::consts::E));
+        let a = iv(0.0, 0.5)
+            .decreasing(f64::acos, (-1.0, 1.0), "acos")
+            .unwrap();
+        assert!(a.contains(FRAC_PI_2) && a.contains(0.5f64.acos()));
+        assert!(iv(0.0, 2.0)
+            .increasing(f64::asin, (-1.0, 1.0), "asin")
+            .is_err());
+        assert_eq!(iv(-2.0, 1.0).powf(2.0).unwrap().lo, 0.0);
+        assert!(iv(-2.0, -1.0).powf(3.0).unwrap().contains(-8.0));
+        assert!(iv(-2.0, 1.0).powf(0.5).is_err());
+        assert_eq!(iv(2.0, 3.0).powf(10.0).unwrap(), iv(1024.0, 59049.0));
+        // 1.25^3001 is 6.71350067900075874...e290, which powi misses.
+        let p = iv(1.25, 1.25).powf(3001.0).unwrap();
+        assert!(p.lo <= 6.713500679000758e290 && 6.713500679000759e290 <= p.hi);
+        let p = iv(-1.25, -1.25).powf(3001.0).unwrap();
+        assert!(p.lo <= -6.713500679000759e290 && -6.713500679000758e290 <= p.hi);
+        let p = iv(2.0, 3.0).pow(iv(1.0, 2.0)).unwrap();
+        assert!(p.contains(2.0) && p.contains(9.0));
+        let a = iv(1.0, 2.0).atan2(iv(1.0, 2.0));
+        assert!(a.contains(0.5f64.atan()) && a.contains(2f64.atan()));
+        assert!(a.contains(FRAC_PI_2 / 2.0) && a.hi < 1.2);
+        assert!(iv(1.0, 2.0)
+            .atan2(iv(-2.0, -1.0))
+            .contains(2f64.atan2(-1.0)));
+        // Across the cut, or around the origin, it's every angle.
+        let full = iv(-1.0, 1.0).atan2(iv(-2.0, -1.0));
+        assert!(full.contains(-PI) && full.contains(PI));
+        assert!(iv(-1.0, 1.0).atan2(iv(-1.0, 1.0)).contains(PI));
+    }
+}
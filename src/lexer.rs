@@ -0,0 +1,212 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    /// Anything that isn't a quoted string: numbers, ops, `'strings`, etc.
+    Word(String),
+    /// A double-quoted string with its escapes already processed.
+    Str(String),
+}
+
+/// Splits a line into tokens.
+///
+/// Tokens are separated by any whitespace. `#` starts a comment that runs to
+/// the end of the line and a standalone `(` starts a comment that runs to the
+/// next `)`, Forth style. Double-quoted strings may contain whitespace and the
+/// escapes `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\u{...}`.
+pub fn lex(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.peek().copied() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            break;
+        } else if c == '"' {
+            chars.next();
+            tokens.push(Token::Str(lex_string(&mut chars)?));
+        } else {
+            let word = lex_word(&mut chars);
+            if word == "(" {
+                if !chars.by_ref().any(|c| c == ')') {
+                    return Err("unterminated ( comment".to_owned());
+                }
+            } else {
+                push_word(&mut tokens, &word);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Whether `word` is an interval such as `[1,2]`.
+fn is_interval(word: &str) -> bool {
+    word.len() > 2
+        && word.starts_with('[')
+        && word.ends_with(']')
+        && word.contains(',')
+        && !word[1..word.len() - 1].contains(['[', ']', '{', '}'])
+}
+
+/// Pushes a word, splitting off any brackets or braces stuck to its ends so
+/// `{1 2 3}` and `[dup *]` lex the same as `{ 1 2 3 }` and `[ dup * ]`.
+/// `'strings` and intervals such as `[1,2]` are left whole.
+fn push_word(tokens: &mut Vec<Token>, word: &str) {
+    if word.len() == 1 || word.starts_with('\'') {
+        tokens.push(Token::Word(word.to_owned()));
+        return;
+    }
+    let mut start = 0;
+    while word[start..].starts_with(['[', '{']) {
+        let interval = word[start..]
+            .find(']')
+            .is_some_and(|end| is_interval(&word[start..=start + end]));
+        if interval {
+            break;
+        }
+        start += 1;
+    }
+    let mut end = word.len();
+    while end > start && word[..end].ends_with([']', '}']) && !is_interval(&word[start..end]) {
+        end -= 1;
+    }
+    for c in word[..start].chars() {
+        tokens.push(Token::Word(c.to_string()));
+    }
+    if end > start {
+        tokens.push(Token::Word(word[start..end].to_owned()));
+    }
+    for c in word[end..].chars() {
+        tokens.push(Token::Word(c.to_string()));
+    }
+}
+
+fn lex_word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(c) = chars.peek() {
+        if c.is_whitespace() {
+            break;
+        }
+        word.push(*c);
+        chars.next();
+    }
+    word
+}
+
+fn lex_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("unterminated string".to_owned()),
+            Some('"') => return Ok(s),
+            Some('\\') => s.push(lex_escape(chars)?),
+            Some(c) => s.push(c),
+        }
+    }
+}
+
+fn lex_escape(chars: &mut Peekable<Chars>) -> Result<char, String> {
+    match chars.next() {
+        Some('n') => Ok('\n'),
+        Some('t') => Ok('\t'),
+        Some('r') => Ok('\r'),
+        Some('0') => Ok('\0'),
+        Some('\\') => Ok('\\'),
+        Some('"') => Ok('"'),
+        Some('u') => {
+            if chars.next() != Some('{') {
+                return Err("expected { after \\u".to_owned());
+            }
+            let mut hex = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => hex.push(c),
+                    None => return Err("unterminated \\u{...} escape".to_owned()),
+                }
+            }
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(format!("invalid unicode escape \\u{{{hex}}}"))
+        }
+        Some(c) => Err(format!("unknown escape \\{c}")),
+        None => Err("unterminated string".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod lexer_test {
+    use super::*;
+    fn w(s: &str) -> Token {
+        Token::Word(s.to_owned())
+    }
+    fn s(s: &str) -> Token {
+        Token::Str(s.to_owned())
+    }
+    #[test]
+    pub fn whitespace() {
+        assert_eq!(lex("1  2\t+ \n").unwrap(), vec![w("1"), w("2"), w("+")]);
+        assert_eq!(lex("").unwrap(), vec![]);
+    }
+    #[test]
+    pub fn strings() {
+        assert_eq!(
+            lex(r#""US survey foot" 'ft conv"#).unwrap(),
+            vec![s("US survey foot"), w("'ft"), w("conv")]
+        );
+        assert_eq!(
+            lex(r#""a\tb\n\"c\"\\ \u{e9}""#).unwrap(),
+            vec![s("a\tb\n\"c\"\\ é")]
+        );
+        assert!(lex(r#""open"#).is_err());
+        assert!(lex(r#""\q""#).is_err());
+        assert!(lex(r#""\u{zz}""#).is_err());
+    }
+    #[test]
+    pub fn comments() {
+        assert_eq!(lex("1 2 # + -").unwrap(), vec![w("1"), w("2")]);
+        assert_eq!(
+            lex("1 ( a b -- c ) 2 swap").unwrap(),
+            vec![w("1"), w("2"), w("swap")]
+        );
+        assert_eq!(
+            lex(r##""#not a comment""##).unwrap(),
+            vec![s("#not a comment")]
+        );
+        assert!(lex("1 ( 2").is_err());
+    }
+    #[test]
+    pub fn brackets() {
+        assert_eq!(
+            lex("{1 2} [dup *]").unwrap(),
+            vec![
+                w("{"),
+                w("1"),
+                w("2"),
+                w("}"),
+                w("["),
+                w("dup"),
+                w("*"),
+                w("]")
+            ]
+        );
+        assert_eq!(
+            lex("{{1}} 'x={}").unwrap(),
+            vec![w("{"), w("{"), w("1"), w("}"), w("}"), w("'x={}")]
+        );
+        assert_eq!(
+            lex("[1,2] [[1,2]] {[1,2]}").unwrap(),
+            vec![
+                w("[1,2]"),
+                w("["),
+                w("[1,2]"),
+                w("]"),
+                w("{"),
+                w("[1,2]"),
+                w("}")
+            ]
+        );
+    }
+}
@@ -1,10 +1,35 @@
-#![feature(integer_sign_cast)]
+mod bigint;
+mod complex;
 mod datetime;
+mod decimal;
+mod encoding;
+mod interval;
+mod lexer;
+mod matrix;
+mod measured;
+mod quantity;
+mod rational;
+mod settings;
+mod sigfig;
+mod unitparse;
 mod units;
+use crate::bigint::BigInt;
+use crate::complex::{fmt_float, Complex};
 use crate::datetime::Date;
-use crate::units::{load_units, UnitExpr};
+use crate::decimal::{Decimal, DIV_PLACES};
+use crate::interval::Interval;
+use crate::lexer::{lex, Token};
+use crate::matrix::Matrix;
+use crate::measured::Measured;
+use crate::quantity::Quantity;
+use crate::rational::Rational;
+use crate::settings::{Angle, Rounding, UnitStyle};
+use crate::sigfig::SigFig;
+use crate::units::{load_units, Units};
 use regex::Regex;
+use std::cmp::Ordering;
 use std::f64::consts::{E, FRAC_PI_2, FRAC_PI_4, LN_10, LN_2, PI, SQRT_2, TAU};
+use std::fmt;
 use std::io;
 use std::ops::{BitAnd, BitOr, BitXor};
 use std::str::FromStr;
@@ -16,6 +41,18 @@ enum Cell {
     Word(i64),
     Date(Date),
     Days(i64),
+    Bool(bool),
+    Block(Vec<Token>),
+    List(Vec<Cell>),
+    Matrix(Matrix),
+    Complex(Complex),
+    BigInt(BigInt),
+    Rat(Rational),
+    Decimal(Decimal),
+    Interval(Interval),
+    Measured(Measured),
+    SigFig(SigFig),
+    Quantity(Quantity),
 }
 
 impl Cell {
@@ -26,53 +63,528 @@ impl Cell {
             Cell::Word(_) => "Word".to_owned(),
             Cell::Date(_) => "Date".to_owned(),
             Cell::Days(_) => "Days".to_owned(),
+            Cell::Bool(_) => "Bool".to_owned(),
+            Cell::Block(_) => "Block".to_owned(),
+            Cell::List(_) => "List".to_owned(),
+            Cell::Matrix(_) => "Matrix".to_owned(),
+            Cell::Complex(_) => "Complex".to_owned(),
+            Cell::BigInt(_) => "BigInt".to_owned(),
+            Cell::Rat(_) => "Rat".to_owned(),
+            Cell::Decimal(_) => "Decimal".to_owned(),
+            Cell::Interval(_) => "Interval".to_owned(),
+            Cell::Measured(_) => "Measured".to_owned(),
+            Cell::SigFig(_) => "SigFig".to_owned(),
+            Cell::Quantity(_) => "Quantity".to_owned(),
         }
     }
 
     fn as_num(&self) -> f64 {
         match self {
             Cell::Num(f) => *f,
-            Cell::Str(_) => panic!("Str is not a number!"),
-            Cell::Word(_) => panic!("Word is not a number!"),
-            Cell::Date(_) => panic!("Date is not a number!"),
-            Cell::Days(_) => panic!("Days is not a number!"),
+            c => panic!("{} is not a number!", c.type_name()),
         }
     }
 
     fn as_word(&self) -> i64 {
         match self {
-            Cell::Num(_) => panic!("Num is not a word!"),
-            Cell::Str(_) => panic!("Str is not a word!"),
             Cell::Word(w) => *w,
-            Cell::Date(_) => panic!("Date is not a word!"),
-            Cell::Days(_) => panic!("Days is not a word!"),
+            c => panic!("{} is not a word!", c.type_name()),
         }
     }
 
     fn as_date(&self) -> Date {
         match self {
-            Cell::Num(_) => panic!("Num is not a date!"),
-            Cell::Str(_) => panic!("Str is not a date!"),
-            Cell::Word(_) => panic!("Word is not date!"),
             Cell::Date(d) => d.clone(),
-            Cell::Days(_) => panic!("Days is not a date!"),
+            c => panic!("{} is not a date!", c.type_name()),
+        }
+    }
+
+    fn as_str(&self) -> String {
+        match self {
+            Cell::Str(s) => s.clone(),
+            c => panic!("{} is not a string!", c.type_name()),
         }
     }
 
     fn as_days(&self) -> i64 {
         match self {
-            Cell::Num(_) => panic!("Num is not a days!"),
-            Cell::Str(_) => panic!("Str is not a days!"),
-            Cell::Word(_) => panic!("Word is not a days!"),
-            Cell::Date(_) => panic!("Date is not a days!"),
             Cell::Days(d) => *d,
+            c => panic!("{} is not a days!", c.type_name()),
+        }
+    }
+
+    fn as_bool(&self) -> bool {
+        match self {
+            Cell::Bool(b) => *b,
+            c => panic!("{} is not a bool!", c.type_name()),
+        }
+    }
+
+    fn as_block(&self) -> Vec<Token> {
+        match self {
+            Cell::Block(b) => b.clone(),
+            c => panic!("{} is not a block!", c.type_name()),
+        }
+    }
+
+    fn as_list(&self) -> Vec<Cell> {
+        match self {
+            Cell::List(l) => l.clone(),
+            c => panic!("{} is not a list!", c.type_name()),
+        }
+    }
+
+    fn as_matrix(&self) -> Matrix {
+        match self {
+            Cell::Matrix(m) => m.clone(),
+            c => panic!("{} is not a matrix!", c.type_name()),
+        }
+    }
+
+    fn as_complex(&self) -> Complex {
+        match self {
+            Cell::Complex(c) => *c,
+            c => panic!("{} is not a complex!", c.type_name()),
+        }
+    }
+
+    fn as_bigint(&self) -> BigInt {
+        match self {
+            Cell::BigInt(b) => b.clone(),
+            c => panic!("{} is not a big integer!", c.type_name()),
+        }
+    }
+
+    fn as_rat(&self) -> Rational {
+        match self {
+            Cell::Rat(r) => *r,
+            c => panic!("{} is not a rational!", c.type_name()),
+        }
+    }
+
+    fn as_decimal(&self) -> Decimal {
+        match self {
+            Cell::Decimal(d) => *d,
+            c => panic!("{} is not a decimal!", c.type_name()),
+        }
+    }
+
+    fn as_interval(&self) -> Interval {
+        match self {
+            Cell::Interval(i) => *i,
+            c => panic!("{} is not an interval!", c.type_name()),
+        }
+    }
+
+    fn as_measured(&self) -> Measured {
+        match self {
+            Cell::Measured(m) => m.clone(),
+            c => panic!("{} is not a measurement!", c.type_name()),
+        }
+    }
+
+    fn as_sigfig(&self) -> SigFig {
+        match self {
+            Cell::SigFig(s) => *s,
+            c => panic!("{} is not a sig-fig number!", c.type_name()),
+        }
+    }
+
+    fn as_quantity(&self) -> Quantity {
+        match self {
+            Cell::Quantity(q) => q.clone(),
+            c => panic!("{} is not a quantity!", c.type_name()),
+        }
+    }
+
+    /// Converts to a wider type that ops for `to` can take in its place, e.g.
+    /// a float to a complex.
+    fn promote(&self, to: &str) -> Option<Cell> {
+        match (self, to) {
+            (Cell::Num(n), "Complex") => Some(Cell::Complex(Complex::new(*n, 0.0))),
+            (Cell::Num(n), "BigInt") => BigInt::from_f64(*n).map(Cell::BigInt),
+            (Cell::Num(n), "Rat") => Rational::from_f64(*n).map(Cell::Rat),
+            (Cell::Num(n), "Decimal") => Decimal::from_f64(*n).map(Cell::Decimal),
+            (Cell::Num(n), "Interval") => Some(Cell::Interval(Interval::point(*n))),
+            (Cell::Num(n), "Measured") => Some(Cell::Measured(Measured::exact(*n))),
+            (Cell::Num(n), "SigFig") => Some(Cell::SigFig(SigFig::exact(*n))),
+            (Cell::Num(n), "Quantity") => Some(Cell::Quantity(Quantity::number(*n))),
+            _ => None,
+        }
+    }
+
+    /// Converts to a narrower type for ops that don't take this one, losing
+    /// what made it different, e.g. a sig-fig number to a plain float.
+    fn demote(&self) -> Option<Cell> {
+        match self {
+            Cell::SigFig(s) => Some(Cell::Num(s.value)),
+            _ => None,
+        }
+    }
+
+    /// This cell demoted if it can be, else as it is.
+    fn demoted(&self) -> Cell {
+        self.demote().unwrap_or_else(|| self.clone())
+    }
+
+    /// Orders two cells of the same type. `Ok(None)` means they're unordered,
+    /// e.g. a NaN.
+    fn compare(&self, other: &Cell) -> Result<Option<Ordering>, String> {
+        match (self, other) {
+            (Cell::Num(a), Cell::Num(b)) => Ok(a.partial_cmp(b)),
+            (Cell::Str(a), Cell::Str(b)) => Ok(Some(a.cmp(b))),
+            (Cell::Word(a), Cell::Word(b)) => Ok(Some(a.cmp(b))),
+            (Cell::Date(a), Cell::Date(b)) => Ok(Some(a.cmp(b))),
+            (Cell::Days(a), Cell::Days(b)) => Ok(Some(a.cmp(b))),
+            (Cell::Bool(a), Cell::Bool(b)) => Ok(Some(a.cmp(b))),
+            (Cell::BigInt(a), Cell::BigInt(b)) => Ok(Some(a.cmp(b))),
+            (Cell::Rat(a), Cell::Rat(b)) => Ok(Some(a.cmp(b))),
+            (Cell::Decimal(a), Cell::Decimal(b)) => Ok(Some(a.cmp(b))),
+            (Cell::Measured(a), Cell::Measured(b)) => Ok(a.value.partial_cmp(&b.value)),
+            (Cell::SigFig(a), Cell::SigFig(b)) => Ok(a.value.partial_cmp(&b.value)),
+            (Cell::SigFig(a), Cell::Num(b)) => Ok(a.value.partial_cmp(b)),
+            (Cell::Num(a), Cell::SigFig(b)) => Ok(a.partial_cmp(&b.value)),
+            (Cell::Quantity(a), Cell::Quantity(b)) => a.compare(b),
+            // Ordered only if they don't overlap.
+            (Cell::Interval(a), Cell::Interval(b)) => Ok(if a == b {
+                Some(Ordering::Equal)
+            } else if a.hi < b.lo {
+                Some(Ordering::Less)
+            } else if a.lo > b.hi {
+                Some(Ordering::Greater)
+            } else {
+                None
+            }),
+            (a, b) => Err(format!(
+                "cannot compare {} and {}",
+                a.type_name(),
+                b.type_name()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Num(n) => write!(formatter, "{n}"),
+            Cell::Str(s) => formatter.write_str(s),
+            Cell::Word(w) => write!(formatter, "0x{:x}", w.cast_unsigned()),
+            Cell::Date(d) => {
+                let m: u16 = (&d.month).into();
+                write!(
+                    formatter,
+                    "{:04}-{:02}-{:02}",
+                    d.year,
+                    m + 1,
+                    d.day_of_month
+                )
+            }
+            Cell::Days(d) => write!(formatter, "{d} days"),
+            Cell::Bool(b) => write!(formatter, "{b}"),
+            Cell::Block(b) => {
+                formatter.write_str("[ ")?;
+                for t in b {
+                    match t {
+                        Token::Word(w) => write!(formatter, "{w} ")?,
+                        Token::Str(s) => write!(formatter, "{s:?} ")?,
+                    }
+                }
+                formatter.write_str("]")
+            }
+            Cell::List(l) => {
+                formatter.write_str("{ ")?;
+                for c in l {
+                    match c {
+                        Cell::Str(s) => write!(formatter, "{s:?} ")?,
+                        c => write!(formatter, "{c} ")?,
+                    }
+                }
+                formatter.write_str("}")
+            }
+            Cell::Matrix(m) => {
+                formatter.write_str("M{")?;
+                for (i, r) in m.to_rows().iter().enumerate() {
+                    if i > 0 {
+                        formatter.write_str(" ;")?;
+                    }
+                    for x in r {
+                        write!(formatter, " {x}")?;
+                    }
+                }
+                formatter.write_str(" }")
+            }
+            Cell::Complex(c) => write!(formatter, "{c}"),
+            Cell::BigInt(b) => {
+                let base = settings::get().base;
+                let digits = b.abs().to_string_radix(base);
+                let sign = if b.is_negative() { "-" } else { "" };
+                match base {
+                    2 => write!(formatter, "{sign}0b{digits}"),
+                    8 => write!(formatter, "{sign}0o{digits}"),
+                    16 => write!(formatter, "{sign}0x{digits}"),
+                    _ => write!(formatter, "{sign}{digits}"),
+                }
+            }
+            Cell::Rat(r) => write!(formatter, "{r}"),
+            Cell::Decimal(d) => write!(formatter, "{d}d"),
+            Cell::Interval(i) => write!(formatter, "{i}"),
+            Cell::Measured(m) => write!(formatter, "{m}"),
+            Cell::SigFig(s) => write!(formatter, "{s}"),
+            Cell::Quantity(q) => write!(formatter, "{q}"),
+        }
+    }
+}
+
+/// Parses a float, a `0x` word, a big integer, a rational, a `d` decimal, an
+/// interval, a `1.23(4)` measurement or a complex.
+///
+/// Integers too big for a float to hold exactly, or written with an `n`
+/// suffix, become big integers, as do `0x` numbers too wide for a word. In
+/// sig-fig mode floats keep the significant figures they're written with.
+fn parse_number(s: &str) -> Option<Cell> {
+    if let Some(d) = s.strip_suffix('d').and_then(Decimal::parse) {
+        return Some(Cell::Decimal(d));
+    }
+    if let Some(b) = s.strip_suffix('n').and_then(BigInt::parse) {
+        return Some(Cell::BigInt(b));
+    }
+    if let Some(b) = BigInt::parse(s) {
+        if b.abs() > BigInt::from_i64(1 << f64::MANTISSA_DIGITS) {
+            return Some(Cell::BigInt(b));
+        }
+    }
+    if settings::get().sigfigs {
+        if let Some(f) = SigFig::parse(s) {
+            return Some(Cell::SigFig(f));
+        }
+    }
+    if let Ok(f) = f64::from_str(s) {
+        Some(Cell::Num(f))
+    } else if let Some(h) = s.strip_prefix("0x") {
+        match u64::from_str_radix(h, 16) {
+            Ok(w) => Some(Cell::Word(w.cast_signed())),
+            Err(_) => BigInt::parse_radix(h, 16).map(Cell::BigInt),
+        }
+    } else if let Some(r) = Rational::parse(s) {
+        Some(Cell::Rat(r))
+    } else if let Some(i) = Interval::parse(s) {
+        Some(Cell::Interval(i))
+    } else if let Some(m) = Measured::parse(s) {
+        Some(Cell::Measured(m))
+    } else {
+        Complex::parse(s).map(Cell::Complex)
+    }
+}
+
+/// Reports an error from an op and puts its arguments back on the stack.
+fn op_error(msg: &str, args: &[Cell]) -> Vec<Cell> {
+    println!("{msg}");
+    args.iter().rev().cloned().collect()
+}
+
+/// Pushes whether `v[1]` and `v[0]` are ordered the way `f` wants.
+fn cmp_op(v: &[Cell], f: fn(Option<Ordering>) -> bool) -> Vec<Cell> {
+    match v[1].compare(&v[0]) {
+        Ok(o) => vec![Cell::Bool(f(o))],
+        Err(e) => op_error(&e, v),
+    }
+}
+
+/// The floats in a list, or an error naming the first entry that isn't one.
+fn nums(l: &[Cell]) -> Result<Vec<f64>, String> {
+    l.iter()
+        .map(|c| match c.demoted() {
+            Cell::Num(n) => Ok(n),
+            c => Err(format!("{} in a list of numbers", c.type_name())),
+        })
+        .collect()
+}
+
+/// A float from a one-argument function of a sig-fig number, such as `sin`,
+/// with the argument's figures.
+fn keep_sigfigs(args: &[Cell], result: Vec<Cell>) -> Vec<Cell> {
+    match (args, result.as_slice()) {
+        ([Cell::SigFig(x)], [Cell::Num(y)]) => sf(x.map(|_| *y)),
+        _ => result,
+    }
+}
+
+fn list_result(r: Result<Vec<Cell>, String>, args: &[Cell]) -> Vec<Cell> {
+    match r {
+        Ok(v) => v,
+        Err(e) => op_error(&e, args),
+    }
+}
+
+/// Converts a list of equal-length lists of floats into a matrix.
+fn list_to_matrix(l: &[Cell]) -> Result<Matrix, String> {
+    let rows = l
+        .iter()
+        .map(|r| match r {
+            Cell::List(r) => nums(r),
+            c => Err(format!("{} in a list of rows", c.type_name())),
+        })
+        .collect::<Result<Vec<Vec<f64>>, String>>()?;
+    Matrix::from_rows(rows)
+}
+
+fn matrix_result(r: Result<Matrix, String>, args: &[Cell]) -> Vec<Cell> {
+    list_result(r.map(|m| vec![Cell::Matrix(m)]), args)
+}
+
+/// Whether a real op turned finite floats into a NaN, e.g. `-1 sqrt`.
+fn went_complex(args: &[Cell], result: &[Cell]) -> bool {
+    args.iter()
+        .all(|a| matches!(a, Cell::Num(n) if n.is_finite()))
+        && result
+            .iter()
+            .any(|r| matches!(r, Cell::Num(n) if n.is_nan()))
+}
+
+/// Pushes the result of checked rational arithmetic, or reports the error and
+/// puts the arguments back.
+fn rat_result(r: Option<Rational>, args: &[Cell]) -> Vec<Cell> {
+    match r {
+        Some(r) => vec![Cell::Rat(r)],
+        None => op_error("rational overflow or division by zero", args),
+    }
+}
+
+/// Pushes the result of checked decimal arithmetic, rounded to the fixed
+/// number of places if one is set, or reports the error and puts the
+/// arguments back.
+fn dec_result(r: Option<Decimal>, args: &[Cell]) -> Vec<Cell> {
+    let settings = settings::get();
+    match r {
+        Some(d) => match settings.places {
+            Some(p) => vec![Cell::Decimal(d.round(p, settings.rounding))],
+            None => vec![Cell::Decimal(d)],
+        },
+        None => op_error("decimal overflow or division by zero", args),
+    }
+}
+
+/// `a / b` at the fixed number of places, or at `DIV_PLACES` without
+/// trailing zeros.
+fn dec_div(a: Decimal, b: Decimal) -> Option<Decimal> {
+    let settings = settings::get();
+    match settings.places {
+        Some(p) => a.checked_div(b, p, settings.rounding),
+        None => a
+            .checked_div(b, DIV_PLACES, settings.rounding)
+            .map(Decimal::trimmed),
+    }
+}
+
+/// Pushes an interval result, or reports the error and puts the arguments
+/// back.
+fn iv_result(r: Result<Interval, String>, args: &[Cell]) -> Vec<Cell> {
+    list_result(r.map(|i| vec![Cell::Interval(i)]), args)
+}
+
+/// Multiplies by a conversion factor, widened since it's rarely exact.
+fn iv_scale(i: Interval, k: f64) -> Interval {
+    if k == 1.0 {
+        i
+    } else {
+        i.mul(Interval::new(k.next_down(), k.next_up()))
+    }
+}
+
+fn ms(m: Measured) -> Vec<Cell> {
+    vec![Cell::Measured(m)]
+}
+
+fn sf(s: SigFig) -> Vec<Cell> {
+    vec![Cell::SigFig(s)]
+}
+
+/// Pushes a quantity, or a float if its units have cancelled out, or reports
+/// the error and puts the arguments back.
+fn qty_result(r: Result<Quantity, String>, args: &[Cell]) -> Vec<Cell> {
+    match r {
+        Ok(q) if q.is_number() => vec![Cell::Num(q.number_value())],
+        Ok(q) => vec![Cell::Quantity(q)],
+        Err(e) => op_error(&e, args),
+    }
+}
+
+/// A word op's result, or if it overflows 64 bits, the exact result as a
+/// big integer.
+fn word_result(
+    v: &[Cell],
+    op: fn(i64, i64) -> Option<i64>,
+    exact: fn(&BigInt, &BigInt) -> BigInt,
+) -> Vec<Cell> {
+    let (a, b) = (v[1].as_word(), v[0].as_word());
+    match op(a, b) {
+        Some(w) => vec![Cell::Word(w)],
+        None => vec![Cell::BigInt(exact(
+            &BigInt::from_i64(a),
+            &BigInt::from_i64(b),
+        ))],
+    }
+}
+
+fn cx(c: Complex) -> Vec<Cell> {
+    vec![Cell::Complex(c)]
+}
+
+/// A complex angle in the current angle mode, in radians.
+fn cx_to_radians(z: Complex) -> Complex {
+    Complex::new(to_radians(z.re), to_radians(z.im))
+}
+
+/// A complex angle in radians, in the current angle mode.
+fn cx_from_radians(z: Complex) -> Complex {
+    Complex::new(from_radians(z.re), from_radians(z.im))
+}
+
+/// An angle in the current angle mode, in radians.
+fn to_radians(x: f64) -> f64 {
+    settings::get().angle.to_radians(x)
+}
+
+/// An angle in radians, in the current angle mode.
+fn from_radians(x: f64) -> f64 {
+    settings::get().angle.radians_in(x)
+}
+
+/// Prints words separated by spaces, in lines that fit a terminal.
+fn print_wrapped(words: &[&str]) {
+    let mut line = String::new();
+    for w in words {
+        if !line.is_empty() && line.len() + w.len() >= 80 {
+            println!("{line}");
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(w);
+    }
+    if !line.is_empty() {
+        println!("{line}");
+    }
+}
+
+/// Pushes what a decoder gives, as a string if it's UTF-8 and otherwise as a
+/// list of its bytes, or reports the error and puts the argument back.
+fn decoded(r: Result<Vec<u8>, String>, arg: &Cell) -> Vec<Cell> {
+    match r.map(String::from_utf8) {
+        Ok(Ok(s)) => vec![Cell::Str(s)],
+        Ok(Err(e)) => {
+            let bytes = e.into_bytes().into_iter().map(|b| Cell::Num(b as f64));
+            vec![Cell::List(bytes.collect())]
         }
+        Err(e) => op_error(&e, std::slice::from_ref(arg)),
     }
 }
 
 type OpFcn<'a> = &'a dyn Fn(Vec<Cell>) -> Vec<Cell>;
 type OpType<'a> = (&'a str, Vec<&'a str>, OpFcn<'a>);
-type OpsType<'a> = Vec<(&'a str, Vec<&'a str>, &'a dyn Fn(Vec<Cell>) -> Vec<Cell>)>;
+type OpsType<'a> = Vec<OpType<'a>>;
 type StackType = Vec<Cell>;
 
 fn find_op<'a>(
@@ -80,7 +592,7 @@ fn find_op<'a>(
     ops: &'a OpsType,
     stack: &'a mut StackType,
 ) -> Option<(&'a OpType<'a>, &'a mut StackType)> {
-    for op in ops {
+    'ops: for op in ops {
         if opname != op.0 {
             continue;
         }
@@ -91,8 +603,8 @@ fn find_op<'a>(
             if *t == "*" {
                 continue;
             }
-            if t.to_owned() != stack[stack.len() - i - 1].type_name() {
-                continue;
+            if stack[stack.len() - i - 1].type_name() != *t {
+                continue 'ops;
             }
         }
         return Some((op, stack));
@@ -100,377 +612,2481 @@ fn find_op<'a>(
     None
 }
 
-fn main() -> io::Result<()> {
-    let ops: Vec<(&str, Vec<&str>, &dyn Fn(Vec<Cell>) -> Vec<Cell>)> = vec![
-        ("days", vec!["Num"], &|v| {
-            vec![Cell::Days(v[0].as_num().floor() as i64)]
+struct Calc<'a> {
+    ops: OpsType<'a>,
+    units: Units,
+    date_pattern: Regex,
+    stack: StackType,
+}
+
+/// Returns the index just past the `else` or `then` that closes the branch
+/// starting at `i`, skipping over nested `if`s and blocks. With
+/// `stop_at_else` false, only `then` closes it.
+fn skip_branch(tokens: &[Token], mut i: usize, stop_at_else: bool) -> usize {
+    let mut depth = 0;
+    while i < tokens.len() {
+        if let Token::Word(w) = &tokens[i] {
+            match w.as_str() {
+                "[" => match matching(tokens, i + 1, "[", "]") {
+                    Some(end) => i = end,
+                    None => return tokens.len(),
+                },
+                "if" => depth += 1,
+                "else" if depth == 0 && stop_at_else => return i + 1,
+                "then" if depth == 0 => return i + 1,
+                "then" => depth -= 1,
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Returns the index of the `close` that matches the `open` just before `i`.
+/// A `M{` opens a brace too.
+fn matching(tokens: &[Token], mut i: usize, open: &str, close: &str) -> Option<usize> {
+    let mut depth = 0;
+    while i < tokens.len() {
+        if let Token::Word(w) = &tokens[i] {
+            if w == open || (open == "{" && w == "M{") {
+                depth += 1;
+            } else if w == close {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+impl Calc<'_> {
+    /// Evaluates a sequence of tokens against the stack. Returns false once
+    /// the user has asked to quit.
+    fn eval(&mut self, tokens: &[Token]) -> bool {
+        let mut i = 0;
+        while i < tokens.len() {
+            let t = &tokens[i];
+            i += 1;
+            let s = match t {
+                Token::Str(s) => {
+                    self.stack.push(Cell::Str(s.clone()));
+                    continue;
+                }
+                Token::Word(s) => s.as_str(),
+            };
+            if s == "[" {
+                match matching(tokens, i, "[", "]") {
+                    Some(end) => {
+                        self.stack.push(Cell::Block(tokens[i..end].to_vec()));
+                        i = end + 1;
+                    }
+                    None => {
+                        println!("unterminated [");
+                        return true;
+                    }
+                }
+            } else if s == "{" {
+                // A list holds whatever its contents leave on the stack.
+                let Some(end) = matching(tokens, i, "{", "}") else {
+                    println!("unterminated {{");
+                    return true;
+                };
+                let base = self.stack.len();
+                if !self.eval(&tokens[i..end]) {
+                    return false;
+                }
+                let items = self.stack.split_off(base.min(self.stack.len()));
+                self.stack.push(Cell::List(items));
+                i = end + 1;
+            } else if s == "M{" {
+                // Rows are separated by `;` and each evaluated like a list.
+                let Some(end) = matching(tokens, i, "{", "}") else {
+                    println!("unterminated M{{");
+                    return true;
+                };
+                let mut rows = vec![];
+                for row in tokens[i..end].split(|t| *t == Token::Word(";".to_owned())) {
+                    let base = self.stack.len();
+                    if !self.eval(row) {
+                        return false;
+                    }
+                    rows.push(Cell::List(self.stack.split_off(base.min(self.stack.len()))));
+                }
+                match list_to_matrix(&rows) {
+                    Ok(m) => self.stack.push(Cell::Matrix(m)),
+                    Err(e) => println!("{e}"),
+                }
+                i = end + 1;
+            } else if s == "]" || s == "}" {
+                println!("unmatched {s}");
+            } else if s == "if" {
+                match self.stack.pop() {
+                    Some(Cell::Bool(true)) => {}
+                    Some(Cell::Bool(false)) => i = skip_branch(tokens, i, true),
+                    v => {
+                        println!("if needs a Bool: {v:?}");
+                        self.stack.extend(v);
+                        i = skip_branch(tokens, i, false);
+                    }
+                }
+            } else if s == "else" {
+                // Only reached at the end of a taken `if` branch.
+                i = skip_branch(tokens, i, false);
+            } else if s == "then" {
+            } else if let Some(running) = self.combinator(s) {
+                if !running {
+                    return false;
+                }
+            } else if !self.eval_word(s) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Pops the top `types.len()` entries if their types match, `types[0]`
+    /// being the top of the stack, demoting any that only fit once demoted.
+    /// Otherwise reports what `name` needed and leaves the stack alone.
+    fn pop_args(&mut self, name: &str, types: &[&str]) -> Option<Vec<Cell>> {
+        let n = types.len();
+        let fits = |c: &Cell, t: &str| {
+            t == "*" || c.type_name() == t || c.demote().is_some_and(|d| d.type_name() == t)
+        };
+        let ok = self.stack.len() >= n
+            && types
+                .iter()
+                .enumerate()
+                .all(|(i, t)| fits(&self.stack[self.stack.len() - i - 1], t));
+        if !ok {
+            let mut wanted: Vec<&str> = types.to_vec();
+            wanted.reverse();
+            println!("{name} needs ( {} )", wanted.join(" "));
+            return None;
+        }
+        let mut args = vec![];
+        for t in types {
+            let c = self.stack.pop().unwrap();
+            if *t == "*" || c.type_name() == *t {
+                args.push(c);
+            } else {
+                args.push(c.demote().unwrap());
+            }
+        }
+        Some(args)
+    }
+
+    /// Applies an op from the table to `args` (top of the stack first),
+    /// mapping it over any lists element-wise. Scalars are repeated against
+    /// every element and lists must all be the same length.
+    fn apply_elementwise(&self, name: &str, args: &[Cell]) -> Result<Cell, String> {
+        let lens: Vec<usize> = args
+            .iter()
+            .filter_map(|a| match a {
+                Cell::List(l) => Some(l.len()),
+                _ => None,
+            })
+            .collect();
+        if let Some(n) = lens.first() {
+            if lens.iter().any(|l| l != n) {
+                return Err(format!("{name} on lists of different lengths"));
+            }
+            let items = (0..*n)
+                .map(|i| {
+                    let elem_args: Vec<Cell> = args
+                        .iter()
+                        .map(|a| match a {
+                            Cell::List(l) => l[i].clone(),
+                            a => a.clone(),
+                        })
+                        .collect();
+                    self.apply_elementwise(name, &elem_args)
+                })
+                .collect::<Result<Vec<Cell>, String>>()?;
+            return Ok(Cell::List(items));
+        }
+        let mut tmp: StackType = args.iter().rev().cloned().collect();
+        let types: Vec<String> = args.iter().map(|a| a.type_name()).collect();
+        let mut r = match find_op(name, &self.ops, &mut tmp) {
+            Some((op, _)) if op.1.len() == args.len() => op.2(args.to_vec()),
+            _ => self
+                .apply_promoted(name, args)
+                .ok_or(format!("no {name} for {types:?}"))?,
+        };
+        if r.len() == 1 {
+            Ok(r.remove(0))
+        } else {
+            Err(format!("{name} doesn't give one value for {types:?}"))
+        }
+    }
+
+    /// Runs the first op named `name` that `args` (top of the stack first)
+    /// fit only once some of them are promoted to a wider type.
+    /// Applies `name`'s op for `to`, with every argument promoted to it.
+    fn apply_as(&self, name: &str, args: &[Cell], to: &str) -> Option<Vec<Cell>> {
+        let op = self
+            .ops
+            .iter()
+            .find(|op| op.0 == name && op.1.len() == args.len() && op.1.iter().all(|t| *t == to))?;
+        let promoted: Option<Vec<Cell>> = args.iter().map(|a| a.promote(to)).collect();
+        Some(op.2(promoted?))
+    }
+
+    fn apply_promoted(&self, name: &str, args: &[Cell]) -> Option<Vec<Cell>> {
+        for op in &self.ops {
+            if op.0 != name || op.1.len() != args.len() {
+                continue;
+            }
+            if args
+                .iter()
+                .zip(&op.1)
+                .all(|(a, t)| *t == "*" || a.type_name() == *t)
+            {
+                continue;
+            }
+            let promoted: Option<Vec<Cell>> = args
+                .iter()
+                .zip(&op.1)
+                .map(|(a, t)| {
+                    if *t == "*" || a.type_name() == *t {
+                        Some(a.clone())
+                    } else {
+                        a.promote(t)
+                    }
+                })
+                .collect();
+            if let Some(promoted) = promoted {
+                return Some(op.2(promoted));
+            }
+        }
+        // Failing that, an op for a narrower type, e.g. `sin` on a sig-fig
+        // number.
+        if !args.iter().any(|a| a.demote().is_some()) {
+            return None;
+        }
+        let demoted: Vec<Cell> = args.iter().map(Cell::demoted).collect();
+        match self.find_demoted(name, &demoted) {
+            Some(op) => Some(keep_sigfigs(args, op.2(demoted))),
+            None => self.apply_promoted(name, &demoted),
+        }
+    }
+
+    /// The op named `name` that takes `demoted` exactly as they are.
+    fn find_demoted(&self, name: &str, demoted: &[Cell]) -> Option<&OpType<'_>> {
+        self.ops.iter().find(|op| {
+            op.0 == name
+                && op.1.len() == demoted.len()
+                && demoted
+                    .iter()
+                    .zip(&op.1)
+                    .all(|(a, t)| *t == "*" || a.type_name() == *t)
+        })
+    }
+
+    /// Runs `s` on the top of the stack if it takes them after promotion,
+    /// e.g. `+` on a float and a complex. Returns false if it doesn't apply.
+    fn coerce(&mut self, s: &str) -> bool {
+        for n in self.arities(s) {
+            if self.stack.len() < n {
+                continue;
+            }
+            let args: Vec<Cell> = self.stack.iter().rev().take(n).cloned().collect();
+            if let Some(mut result) = self.apply_promoted(s, &args) {
+                self.express_result(s, &mut result);
+                self.stack.truncate(self.stack.len() - n);
+                self.stack.append(&mut result);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The distinct numbers of arguments taken by the ops named `s`.
+    fn arities(&self, s: &str) -> Vec<usize> {
+        let mut arities: Vec<usize> = self
+            .ops
+            .iter()
+            .filter(|op| op.0 == s && !op.1.is_empty())
+            .map(|op| op.1.len())
+            .collect();
+        arities.sort();
+        arities.dedup();
+        arities
+    }
+
+    /// Runs `s` element-wise if it's an op from the table and a list is among
+    /// the entries it would take. Returns false if it doesn't apply.
+    fn broadcast(&mut self, s: &str) -> bool {
+        for n in self.arities(s) {
+            if self.stack.len() < n {
+                continue;
+            }
+            let args: Vec<Cell> = self.stack.iter().rev().take(n).cloned().collect();
+            if !args.iter().any(|a| matches!(a, Cell::List(_))) {
+                continue;
+            }
+            // Such as `nth` with a sig-fig index, which takes the list whole.
+            let demoted: Vec<Cell> = args.iter().map(Cell::demoted).collect();
+            if self.find_demoted(s, &demoted).is_some() {
+                return false;
+            }
+            match self.apply_elementwise(s, &args) {
+                Ok(c) => {
+                    self.stack.truncate(self.stack.len() - n);
+                    self.stack.push(c);
+                }
+                Err(e) => println!("{e}"),
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Pops `( x1 .. xn n )` as pushed by `split` and friends.
+    fn pop_seq(&mut self, name: &str) -> Option<Vec<Cell>> {
+        let n = self.pop_args(name, &["Num"])?[0].as_num().max(0.0) as usize;
+        if self.stack.len() < n {
+            println!("{name} needs {n} entries, stack has {}", self.stack.len());
+            self.stack.push(Cell::Num(n as f64));
+            return None;
+        }
+        Some(self.stack.split_off(self.stack.len() - n))
+    }
+
+    /// Runs the quotation combinators. Returns `None` if `s` isn't one, or
+    /// whether to keep running.
+    fn combinator(&mut self, s: &str) -> Option<bool> {
+        let running = match s {
+            "call" => {
+                let Some(v) = self.pop_args(s, &["Block"]) else {
+                    return Some(true);
+                };
+                self.eval(&v[0].as_block())
+            }
+            "ifte" => {
+                let Some(v) = self.pop_args(s, &["Block", "Block", "Bool"]) else {
+                    return Some(true);
+                };
+                let branch = if v[2].as_bool() { &v[1] } else { &v[0] };
+                self.eval(&branch.as_block())
+            }
+            "times" => {
+                let Some(v) = self.pop_args(s, &["Block", "Num"]) else {
+                    return Some(true);
+                };
+                let q = v[0].as_block();
+                (0..v[1].as_num().max(0.0) as usize).all(|_| self.eval(&q))
+            }
+            "while" => {
+                let Some(v) = self.pop_args(s, &["Block", "Block"]) else {
+                    return Some(true);
+                };
+                let (pred, body) = (v[1].as_block(), v[0].as_block());
+                loop {
+                    if !self.eval(&pred) {
+                        break false;
+                    }
+                    match self.stack.pop() {
+                        Some(Cell::Bool(true)) => {}
+                        Some(Cell::Bool(false)) => break true,
+                        c => {
+                            println!("while predicate must leave a Bool: {c:?}");
+                            self.stack.extend(c);
+                            break true;
+                        }
+                    }
+                    if !self.eval(&body) {
+                        break false;
+                    }
+                }
+            }
+            "dip" => {
+                let Some(v) = self.pop_args(s, &["Block", "*"]) else {
+                    return Some(true);
+                };
+                let running = self.eval(&v[0].as_block());
+                self.stack.push(v[1].clone());
+                running
+            }
+            "keep" => {
+                let Some(v) = self.pop_args(s, &["Block", "*"]) else {
+                    return Some(true);
+                };
+                self.stack.push(v[1].clone());
+                let running = self.eval(&v[0].as_block());
+                self.stack.push(v[1].clone());
+                running
+            }
+            "bi" => {
+                let Some(v) = self.pop_args(s, &["Block", "Block", "*"]) else {
+                    return Some(true);
+                };
+                self.stack.push(v[2].clone());
+                self.eval(&v[1].as_block()) && {
+                    self.stack.push(v[2].clone());
+                    self.eval(&v[0].as_block())
+                }
+            }
+            "map" | "filter" => {
+                let Some(q) = self.pop_args(s, &["Block"]) else {
+                    return Some(true);
+                };
+                let q = q[0].as_block();
+                let list = matches!(self.stack.last(), Some(Cell::List(_)));
+                let xs = if list {
+                    self.stack.pop().unwrap().as_list()
+                } else if let Some(xs) = self.pop_seq(s) {
+                    xs
+                } else {
+                    self.stack.push(Cell::Block(q));
+                    return Some(true);
+                };
+                let base = self.stack.len();
+                let mut running = true;
+                for x in xs {
+                    let before = self.stack.len();
+                    self.stack.push(x.clone());
+                    running = self.eval(&q);
+                    if !running {
+                        break;
+                    }
+                    if s == "filter" {
+                        match self.stack.pop() {
+                            Some(Cell::Bool(keep)) => {
+                                self.stack.truncate(before);
+                                if keep {
+                                    self.stack.push(x);
+                                }
+                            }
+                            c => {
+                                println!("filter predicate must leave a Bool: {c:?}");
+                                self.stack.extend(c);
+                                return Some(true);
+                            }
+                        }
+                    }
+                }
+                if list {
+                    let items = self.stack.split_off(base.min(self.stack.len()));
+                    self.stack.push(Cell::List(items));
+                } else {
+                    let n = self.stack.len().saturating_sub(base);
+                    self.stack.push(Cell::Num(n as f64));
+                }
+                running
+            }
+            "fold" => {
+                let Some(v) = self.pop_args(s, &["Block", "*"]) else {
+                    return Some(true);
+                };
+                let xs = if let Some(Cell::List(_)) = self.stack.last() {
+                    self.stack.pop().unwrap().as_list()
+                } else if let Some(xs) = self.pop_seq(s) {
+                    xs
+                } else {
+                    self.stack.push(v[1].clone());
+                    self.stack.push(v[0].clone());
+                    return Some(true);
+                };
+                let q = v[0].as_block();
+                self.stack.push(v[1].clone());
+                xs.into_iter().all(|x| {
+                    self.stack.push(x);
+                    self.eval(&q)
+                })
+            }
+            _ => return None,
+        };
+        Some(running)
+    }
+
+    /// Gives the quantity from arithmetic op `name` in the units the
+    /// settings ask for.
+    fn express_result(&self, name: &str, result: &mut [Cell]) {
+        if !["+", "-", "*", "/", "pow", "sq", "1/", "recip"].contains(&name) {
+            return;
+        }
+        if let [Cell::Quantity(q)] = result {
+            let style = settings::get().units;
+            if let Some(e) = self.units.express(&q.reduced().dim, &style) {
+                if let Ok(c) = q.convert(e.0, e.1) {
+                    *q = c;
+                }
+            }
+        }
+    }
+
+    /// `q` in the mixed units of `target`, such as `ft;in`, or `None` if
+    /// it isn't a list of units.
+    fn split(&self, q: &Quantity, target: &str) -> Option<Result<Vec<Quantity>, String>> {
+        let list = self.units.unit_list(target)?;
+        let units: Result<Vec<_>, _> = list.iter().map(|u| self.units.quantity_unit(u)).collect();
+        Some(units.and_then(|units| q.split(units)))
+    }
+
+    fn eval_word(&mut self, s: &str) -> bool {
+        if let Some(n) = parse_number(s) {
+            self.stack.push(n);
+        } else if self.date_pattern.is_match(s) {
+            let mut date_parts = s.split("-");
+            self.stack.push(Cell::Date(Date::new(
+                i16::from_str(date_parts.next().unwrap()).unwrap(),
+                (u16::from_str(date_parts.next().unwrap()).unwrap() - 1).into(),
+                u8::from_str(date_parts.next().unwrap()).unwrap(),
+            )));
+        } else if let Some(s) = s.strip_prefix("'") {
+            self.stack.push(Cell::Str(s.to_string()));
+        } else if let Some((op, stack)) = find_op(s, &self.ops, &mut self.stack) {
+            let mut params = vec![];
+            let plen = op.1.len();
+            for _ in 1..=plen {
+                params.push(stack.pop().unwrap());
+            }
+            let mut result = op.2(params.clone());
+            if settings::get().complex && went_complex(&params, &result) {
+                if let Some(r) = self.apply_as(s, &params, "Complex") {
+                    result = r;
+                }
+            }
+            self.express_result(s, &mut result);
+            self.stack.append(&mut result);
+        } else if self.broadcast(s) || self.coerce(s) {
+        } else if s == "->list" {
+            if let Some(items) = self.pop_seq(s) {
+                self.stack.push(Cell::List(items));
+            }
+        } else if s == "join" {
+            let sep = self.stack.pop();
+            let n = self.stack.pop();
+            if let (Some(Cell::Str(sep)), Some(Cell::Num(n))) =
+                (&sep, &n.as_ref().map(Cell::demoted))
+            {
+                let n = n.max(0.0) as usize;
+                if self.stack.len() < n {
+                    println!("join needs {n} entries, stack has {}", self.stack.len());
+                    self.stack.push(Cell::Num(n as f64));
+                    self.stack.push(Cell::Str(sep.clone()));
+                } else {
+                    let parts: Vec<String> = self
+                        .stack
+                        .split_off(self.stack.len() - n)
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect();
+                    self.stack.push(Cell::Str(parts.join(sep)));
+                }
+            } else {
+                println!("join needs a count and a separator: {n:?} {sep:?}");
+                self.stack.extend(n);
+                self.stack.extend(sep);
+            }
+        } else if s == "fmt" {
+            if let Some(Cell::Str(template)) = self.stack.last().cloned() {
+                let holes = template.matches("{}").count();
+                if self.stack.len() <= holes {
+                    println!(
+                        "fmt needs {holes} values, stack has {}",
+                        self.stack.len() - 1
+                    );
+                } else {
+                    self.stack.pop();
+                    let args = self.stack.split_off(self.stack.len() - holes);
+                    let mut out = String::new();
+                    let mut rest = template.as_str();
+                    for a in args {
+                        let (before, after) = rest.split_once("{}").unwrap();
+                        out.push_str(before);
+                        out.push_str(&a.to_string());
+                        rest = after;
+                    }
+                    out.push_str(rest);
+                    self.stack.push(Cell::Str(out));
+                }
+            } else {
+                println!("fmt needs a template string");
+            }
+        } else if s == "conv" {
+            let a = self.stack.pop();
+            let b = self.stack.pop();
+            if let Some(Cell::Str(a)) = a {
+                if let Some(Cell::Str(b)) = b {
+                    let from = self.units.quantity_unit(&b);
+                    let from = from.map(|(unit, reduced)| Quantity::new(1.0, unit, reduced));
+                    let parts = match from {
+                        Ok(q) => self.split(&q, &a),
+                        Err(e) => self.units.unit_list(&a).map(|_| Err(e)),
+                    };
+                    let converted = match parts {
+                        Some(parts) => parts.map(|p| p.iter().map(|p| p.value).collect()),
+                        None => self.units.convert(&b, &a).map(|c| vec![c.as_f64()]),
+                    };
+                    match converted {
+                        Ok(c) => self.stack.extend(c.into_iter().map(Cell::Num)),
+                        Err(e) => {
+                            println!("{e}");
+                            self.stack.push(Cell::Str(b));
+                            self.stack.push(Cell::Str(a));
+                        }
+                    }
+                } else {
+                    println!("2nd not a String: {b:?}");
+                }
+            } else {
+                println!("1st not a String: {a:?}");
+            }
+        } else if s == "u" || s == "->" {
+            let a = self.stack.pop();
+            let b = self.stack.pop();
+            let q = match (&b.as_ref().map(Cell::demoted), &a) {
+                // An absolute temperature or the like, in the function's units.
+                (Some(Cell::Num(n)), Some(Cell::Str(u)))
+                    if s == "u" && self.units.is_function(u) =>
+                {
+                    let output = self.units.function_output(u).unwrap_or("1");
+                    self.units.apply(u, *n).and_then(|r| {
+                        let (unit, reduced) = self.units.quantity_unit(output)?;
+                        Quantity::from_reduced(&r, unit, reduced)
+                    })
+                }
+                (Some(Cell::Quantity(q)), Some(Cell::Str(u)))
+                    if s == "->" && self.units.unit_list(u).is_some() =>
+                {
+                    match self.split(q, u).unwrap() {
+                        Ok(parts) => {
+                            self.stack.extend(parts.into_iter().map(Cell::Quantity));
+                            return true;
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                (Some(Cell::Quantity(q)), Some(Cell::Str(u)))
+                    if s == "->" && self.units.is_function(u) =>
+                {
+                    match self.units.invert(u, &q.reduced()) {
+                        Ok(x) => {
+                            self.stack.push(Cell::Num(x));
+                            return true;
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                (Some(Cell::Num(n)), Some(Cell::Str(u))) if s == "u" => self
+                    .units
+                    .quantity_unit(u)
+                    .map(|(unit, reduced)| Quantity::new(*n, unit, reduced)),
+                (Some(Cell::Quantity(q)), Some(Cell::Str(u))) if s == "->" => self
+                    .units
+                    .quantity_unit(u)
+                    .and_then(|(unit, reduced)| q.convert(unit, reduced)),
+                _ => Err(format!(
+                    "{s} needs a {} and a unit: {b:?} {a:?}",
+                    if s == "u" { "number" } else { "quantity" }
+                )),
+            };
+            match q {
+                Ok(q) => self.stack.append(&mut qty_result(Ok(q), &[])),
+                Err(e) => {
+                    println!("{e}");
+                    self.stack.extend(b);
+                    self.stack.extend(a);
+                }
+            }
+        } else if s == "qty" {
+            match self.stack.pop() {
+                Some(Cell::Str(text)) => match Quantity::parse(&text, &self.units) {
+                    Ok(q) => self.stack.append(&mut qty_result(Ok(q), &[])),
+                    Err(e) => {
+                        println!("{e}");
+                        self.stack.push(Cell::Str(text));
+                    }
+                },
+                a => {
+                    println!("qty needs a string: {a:?}");
+                    self.stack.extend(a);
+                }
+            }
+        } else if s == "units" || s == "conformable" || s == "usearch" || s == "udef" {
+            match self.stack.pop() {
+                Some(Cell::Str(a)) => {
+                    let found = match s {
+                        "units" if a == "all" => Ok(self.units.names()),
+                        "units" => self
+                            .units
+                            .dimension_of(&a)
+                            .map(|d| self.units.conformable(&d)),
+                        "conformable" => self.units.parse(&a).map(|u| {
+                            let mut c = self.units.conformable(&u.dim);
+                            c.retain(|n| *n != a);
+                            c
+                        }),
+                        "usearch" => Ok(self.units.search(&a)),
+                        _ => self.units.definition(&a).map(|lines| {
+                            for l in &lines {
+                                println!("{l}");
+                            }
+                            vec![]
+                        }),
+                    };
+                    match found {
+                        Ok(names) => print_wrapped(&names),
+                        Err(e) => {
+                            println!("{e}");
+                            self.stack.push(Cell::Str(a));
+                        }
+                    }
+                }
+                a => {
+                    println!("{s} needs a string: {a:?}");
+                    self.stack.extend(a);
+                }
+            }
+        } else if s == "defunit" {
+            let a = self.stack.pop();
+            let b = self.stack.pop();
+            match (b, a) {
+                (Some(Cell::Str(name)), Some(Cell::Str(def))) => {
+                    if let Err(e) = self.units.define_unit(&name, &def) {
+                        println!("{e}");
+                        self.stack.push(Cell::Str(name));
+                        self.stack.push(Cell::Str(def));
+                    }
+                }
+                (b, a) => {
+                    println!("defunit needs a name and a definition: {b:?} {a:?}");
+                    self.stack.extend(b);
+                    self.stack.extend(a);
+                }
+            }
+        } else if s == "dimension" {
+            match self.stack.pop() {
+                Some(Cell::Str(u)) => match self.units.dimension(&u) {
+                    Ok(d) => self.stack.push(Cell::Str(d)),
+                    Err(e) => {
+                        println!("{e}");
+                        self.stack.push(Cell::Str(u));
+                    }
+                },
+                a => {
+                    println!("dimension needs a unit: {a:?}");
+                    self.stack.extend(a);
+                }
+            }
+        } else if s == "pl" {
+            let v = self.stack.last();
+            if let Some(v) = v {
+                match v {
+                    Cell::Num(v) => println!("N {v:?}"),
+                    Cell::Date(v) => println!("D {v:?}"),
+                    Cell::Days(v) => println!("Ds {v:?}"),
+                    Cell::Word(v) => {
+                        print!("W b");
+                        for i in (0..=56).rev().step_by(8) {
+                            let p1 = (v >> (i + 4)) & 0xf;
+                            let p2 = (v >> i) & 0xf;
+                            print!("{p1:04b} {p2:04b}  ");
+                        }
+                        println!();
+                        print!("W x");
+                        for i in (0..=56).rev().step_by(8) {
+                            let p = (v >> i) & 0xff;
+                            print!("{p:02X} ");
+                        }
+                        println!();
+                        println!("W {v}i64");
+                        let u = v.cast_unsigned();
+                        println!("W {u}u64");
+                        // let f = f64::from_bits(v.cast_unsigned());
+                        // println!("W {f}f64")
+                    }
+                    Cell::Str(v) => println!("S {v:?}"),
+                    Cell::Bool(v) => println!("B {v:?}"),
+                    Cell::Block(_) => println!("K {v}"),
+                    Cell::List(_) => println!("L {v}"),
+                    Cell::Matrix(m) => println!("M {}", m.to_string().replace("\n", "\n  ")),
+                    Cell::Complex(c) => println!("C {c}"),
+                    Cell::BigInt(b) => {
+                        println!("I {}", b.to_string_radix(10));
+                        println!("I x{}", b.to_string_radix(16));
+                        println!("I {} digits", b.abs().to_string_radix(10).len());
+                    }
+                    Cell::Rat(r) => {
+                        println!("R {}", r.mixed());
+                        println!("R {r}");
+                        println!("R {:?}", r.as_f64());
+                    }
+                    Cell::Decimal(d) => {
+                        println!("Dc {d}");
+                        println!("Dc {:?}", d.to_f64());
+                    }
+                    Cell::Interval(i) => {
+                        println!("Iv {i}");
+                        println!("Iv {:?} ± {:?}", i.mid(), i.width() / 2.0);
+                    }
+                    Cell::SigFig(f) => {
+                        println!("Sf {f}");
+                        println!("Sf {:?}", f.value);
+                        match f.digits() {
+                            Some(d) => println!("Sf {d} significant figures"),
+                            None => println!("Sf exact"),
+                        }
+                    }
+                    Cell::Quantity(q) => {
+                        println!("Q {q}");
+                        println!("Q {:?} {}", q.value, q.unit);
+                    }
+                    Cell::Measured(m) => {
+                        println!("Ms {}", m.plus_minus());
+                        println!("Ms {:?} ± {:?}", m.value, m.sigma());
+                        // Relative to zero, the uncertainty is meaningless.
+                        if m.value != 0.0 {
+                            println!(
+                                "Ms {}% relative",
+                                fmt_float(100.0 * m.sigma() / m.value.abs())
+                            );
+                        }
+                    }
+                };
+            }
+        } else if s == "p" || s == "pd" {
+            let v = if s == "pd" {
+                self.stack.pop()
+            } else {
+                // Barf
+                self.stack.last().cloned()
+            };
+            if let Some(v) = v {
+                match v {
+                    Cell::Num(v) => println!("N {v:?}"),
+                    Cell::Date(v) => println!("D {v:?}"),
+                    Cell::Days(v) => println!("Ds {v:?}"),
+                    Cell::Word(v) => {
+                        print!("W b");
+                        for i in (0..=56).rev().step_by(8) {
+                            let p1 = (v >> (i + 4)) & 0xf;
+                            let p2 = (v >> i) & 0xf;
+                            print!("{p1:04b} {p2:04b}  ");
+                        }
+                        println!();
+                    }
+                    Cell::Str(v) => println!("S {v:?}"),
+                    Cell::Bool(v) => println!("B {v:?}"),
+                    Cell::Block(_) => println!("K {v}"),
+                    Cell::List(_) => println!("L {v}"),
+                    Cell::Matrix(m) => println!("M {}", m.to_string().replace("\n", "\n  ")),
+                    Cell::Complex(c) => println!("C {c}"),
+                    Cell::BigInt(_) => println!("I {v}"),
+                    Cell::Rat(r) => println!("R {}", r.mixed()),
+                    Cell::Decimal(d) => println!("Dc {d}"),
+                    Cell::Interval(i) => println!("Iv {i}"),
+                    Cell::Measured(m) => println!("Ms {}", m.plus_minus()),
+                    Cell::SigFig(f) => println!("Sf {f}"),
+                    Cell::Quantity(q) => println!("Q {q}"),
+                };
+            }
+        } else if s == "clr" {
+            while self.stack.pop().is_some() {}
+        } else if s == "ps" {
+            let l = self.stack.len();
+            println!("Stack is {l} entries deep");
+            for (i, e) in self.stack.iter().rev().enumerate() {
+                println!(" {i}: {e:?}");
+            }
+            println!();
+        } else if s == "status" {
+            println!("{}", settings::get());
+        } else if s == "q" {
+            return false;
+        } else {
+            self.stack.push(Cell::Str(s.to_owned()));
+        }
+        true
+    }
+}
+
+/// Every op, by name and the types it takes, `types[0]` being the top of the
+/// stack.
+fn ops() -> OpsType<'static> {
+    vec![
+        ("days", vec!["Num"], &|v| {
+            vec![Cell::Days(v[0].as_num().floor() as i64)]
+        }),
+        ("weeks", vec!["Num"], &|v| {
+            vec![Cell::Days(7 * v[0].as_num().floor() as i64)]
+        }),
+        ("+", vec!["Days", "Date"], &|v| {
+            vec![Cell::Date(v[1].as_date().add_days(v[0].as_days() as u16))]
+        }),
+        ("dow", vec!["Date"], &|a| {
+            vec![Cell::Str(format!("{:?}", a[0].as_date().dow()))]
+        }),
+        ("doy", vec!["Date"], &|a| {
+            vec![Cell::Num(a[0].as_date().doy() as f64)]
+        }),
+        ("+", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(v[1].as_num() + v[0].as_num())]
+        }),
+        ("-", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(v[1].as_num() - v[0].as_num())]
+        }),
+        ("*", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(v[1].as_num() * v[0].as_num())]
+        }),
+        ("/", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(v[1].as_num() / v[0].as_num())]
+        }),
+        ("pow", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(f64::powf(v[1].as_num(), v[0].as_num()))]
+        }),
+        ("atan2", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(from_radians(f64::atan2(
+                v[1].as_num(),
+                v[0].as_num(),
+            )))]
+        }),
+        ("hypot", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(f64::hypot(v[1].as_num(), v[0].as_num()))]
+        }),
+        ("sin", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::sin(to_radians(v[0].as_num())))]
+        }),
+        ("cos", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::cos(to_radians(v[0].as_num())))]
+        }),
+        ("tan", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::tan(to_radians(v[0].as_num())))]
+        }),
+        ("asin", vec!["Num"], &|v| {
+            vec![Cell::Num(from_radians(f64::asin(v[0].as_num())))]
+        }),
+        ("acos", vec!["Num"], &|v| {
+            vec![Cell::Num(from_radians(f64::acos(v[0].as_num())))]
+        }),
+        ("atan", vec!["Num"], &|v| {
+            vec![Cell::Num(from_radians(f64::atan(v[0].as_num())))]
+        }),
+        ("sinh", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::sinh(v[0].as_num()))]
+        }),
+        ("cosh", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::cosh(v[0].as_num()))]
+        }),
+        ("tanh", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::tanh(v[0].as_num()))]
+        }),
+        ("asinh", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::asinh(v[0].as_num()))]
+        }),
+        ("acosh", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::acosh(v[0].as_num()))]
+        }),
+        ("atanh", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::atanh(v[0].as_num()))]
+        }),
+        ////
+        ("d2r", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::to_radians(v[0].as_num()))]
+        }),
+        ("r2d", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::to_degrees(v[0].as_num()))]
+        }),
+        ////
+        ("1/", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::recip(v[0].as_num()))]
+        }),
+        ("recip", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::recip(v[0].as_num()))]
+        }),
+        ////
+        ("ln", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::ln(v[0].as_num()))]
+        }),
+        ("ln1+", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::ln_1p(v[0].as_num()))]
+        }),
+        ("log10", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::log10(v[0].as_num()))]
+        }),
+        ("log2", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::log2(v[0].as_num()))]
+        }),
+        ("exp", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::exp(v[0].as_num()))]
+        }),
+        ("exp-1", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::exp_m1(v[0].as_num()))]
+        }),
+        ("sqrt", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::sqrt(v[0].as_num()))]
+        }),
+        ("cbrt", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::cbrt(v[0].as_num()))]
+        }),
+        ("sq", vec!["Num"], &|a| {
+            vec![Cell::Num(a[0].as_num() * a[0].as_num())]
+        }),
+        ("cb", vec!["Num"], &|a| {
+            vec![Cell::Num(a[0].as_num() * a[0].as_num() * a[0].as_num())]
+        }),
+        ////
+        ("abs", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::abs(v[0].as_num()))]
+        }),
+        ("ceil", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::ceil(v[0].as_num()))]
+        }),
+        ("floor", vec!["Num"], &|v| {
+            vec![Cell::Num(f64::floor(v[0].as_num()))]
+        }),
+        ("pi", vec![], &|_| vec![Cell::Num(PI)]),
+        ("e", vec![], &|_| vec![Cell::Num(E)]),
+        ("sqrt2", vec![], &|_| vec![Cell::Num(SQRT_2)]),
+        ("ln2", vec![], &|_| vec![Cell::Num(LN_2)]),
+        ("ln10", vec![], &|_| vec![Cell::Num(LN_10)]),
+        ("tau", vec![], &|_| vec![Cell::Num(TAU)]),
+        ("pi/2", vec![], &|_| vec![Cell::Num(FRAC_PI_2)]),
+        ("pi/4", vec![], &|_| vec![Cell::Num(FRAC_PI_4)]),
+        ("drop", vec!["*"], &|_v| vec![]),
+        ("swap", vec!["*", "*"], &|v| {
+            vec![v[0].clone(), v[1].clone()]
+        }),
+        ("rot", vec!["*", "*", "*"], &|v| {
+            vec![v[0].clone(), v[2].clone(), v[1].clone()]
+        }),
+        ("dup", vec!["*"], &|v| vec![v[0].clone(), v[0].clone()]),
+        ////
+        ("w", vec!["Num"], &|v| {
+            if let Cell::Num(n) = v[0] {
+                vec![Cell::Word(n as i64)]
+            } else {
+                let v = &v[0];
+                println!("not a number: {v:?}; cannot convert to word");
+                vec![]
+            }
+        }),
+        ////
+        ("dms2dd", vec!["Num", "Num", "Num"], &|v| {
+            if let Cell::Num(d) = v[2] {
+                if let Cell::Num(m) = v[1] {
+                    if let Cell::Num(s) = v[0] {
+                        vec![Cell::Num(d + m / 60.0 + s / 3600.0)]
+                    } else {
+                        let s = &v[0];
+                        println!("s was not a number: {s:?}");
+                        vec![]
+                    }
+                } else {
+                    let m = &v[1];
+                    println!("m was not a number: {m:?}");
+                    vec![]
+                }
+            } else {
+                let d = &v[2];
+                println!("d was not a number: {d:?}");
+                vec![]
+            }
+        }),
+        ("dd2dms", vec!["Num"], &|v| {
+            if let Cell::Num(mut v) = v[0] {
+                let d = v.floor();
+                v = (v - d) * 60.0;
+                let m = v.floor();
+                v = (v - m) * 60.0;
+                let s = v;
+                vec![Cell::Num(d), Cell::Num(m), Cell::Num(s)]
+            } else {
+                let d = &v[0];
+                println!("d was not a number: {d:?}");
+                vec![]
+            }
+        }),
+        ("+", vec!["Word", "Word"], &|v| {
+            word_result(&v, i64::checked_add, |a, b| a + b)
+        }),
+        ("-", vec!["Word", "Word"], &|v| {
+            word_result(&v, i64::checked_sub, |a, b| a - b)
+        }),
+        ("*", vec!["Word", "Word"], &|v| {
+            word_result(&v, i64::checked_mul, |a, b| a * b)
+        }),
+        ("/", vec!["Word", "Word"], &|v| {
+            if v[0].as_word() == 0 {
+                return op_error("division by zero", &v);
+            }
+            // Only MIN / -1 overflows.
+            word_result(&v, i64::checked_div, |a, _| -a.clone())
+        }),
+        (
+            "mod",
+            vec!["Word", "Word"],
+            &|v| match v[1].as_word().checked_rem(v[0].as_word()) {
+                Some(r) => vec![Cell::Word(r)],
+                None if v[0].as_word() == 0 => op_error("division by zero", &v),
+                None => vec![Cell::Word(0)],
+            },
+        ),
+        ("&", vec!["Word", "Word"], &|v| {
+            vec![Cell::Word(i64::bitand(v[1].as_word(), v[0].as_word()))]
+        }),
+        ("^", vec!["Word", "Word"], &|v| {
+            vec![Cell::Word(i64::bitxor(v[1].as_word(), v[0].as_word()))]
+        }),
+        ("|", vec!["Word", "Word"], &|v| {
+            vec![Cell::Word(i64::bitor(v[1].as_word(), v[0].as_word()))]
+        }),
+        ("sl", vec!["Word", "Word"], &|v| {
+            vec![Cell::Word(v[1].as_word() << v[0].as_word())]
+        }),
+        ("asr", vec!["Word", "Word"], &|v| {
+            vec![Cell::Word(v[1].as_word() >> v[0].as_word())]
+        }),
+        ("lsr", vec!["Word", "Word"], &|v| {
+            vec![Cell::Word(i64::from_be_bytes(
+                ((u64::from_be_bytes(v[1].as_word().to_be_bytes())) >> v[0].as_word())
+                    .to_be_bytes(),
+            ))]
+        }),
+        ("~", vec!["Word"], &|v| vec![Cell::Word(!v[0].as_word())]),
+        ////
+        ("b64enc", vec!["Str"], &|v| {
+            vec![Cell::Str(encoding::base64_encode(&v[0].as_str()))]
+        }),
+        ("b64dec", vec!["Str"], &|v| {
+            decoded(encoding::base64_decode(&v[0].as_str()), &v[0])
+        }),
+        ("b64uenc", vec!["Str"], &|v| {
+            vec![Cell::Str(encoding::base64url_encode(&v[0].as_str()))]
+        }),
+        ("b64udec", vec!["Str"], &|v| {
+            decoded(encoding::base64url_decode(&v[0].as_str()), &v[0])
+        }),
+        ("b32enc", vec!["Str"], &|v| {
+            vec![Cell::Str(encoding::base32_encode(&v[0].as_str()))]
+        }),
+        ("b32dec", vec!["Str"], &|v| {
+            decoded(encoding::base32_decode(&v[0].as_str()), &v[0])
+        }),
+        ("hexenc", vec!["Str"], &|v| {
+            vec![Cell::Str(encoding::hex_encode(&v[0].as_str()))]
+        }),
+        ("hexdec", vec!["Str"], &|v| {
+            decoded(encoding::hex_decode(&v[0].as_str()), &v[0])
+        }),
+        ("pctenc", vec!["Str"], &|v| {
+            vec![Cell::Str(encoding::percent_encode(&v[0].as_str()))]
+        }),
+        ("pctdec", vec!["Str"], &|v| {
+            decoded(encoding::percent_decode(&v[0].as_str()), &v[0])
+        }),
+        ("urlenc", vec!["Str"], &|v| {
+            vec![Cell::Str(encoding::url_encode(&v[0].as_str()))]
+        }),
+        ("urldec", vec!["Str"], &|v| {
+            decoded(encoding::url_decode(&v[0].as_str()), &v[0])
+        }),
+        ("qpenc", vec!["Str"], &|v| {
+            vec![Cell::Str(encoding::qp_encode(&v[0].as_str()))]
+        }),
+        ("qpdec", vec!["Str"], &|v| {
+            decoded(encoding::qp_decode(&v[0].as_str()), &v[0])
+        }),
+        ////
+        ("cat", vec!["Str", "Str"], &|v| {
+            vec![Cell::Str(v[1].as_str() + &v[0].as_str())]
+        }),
+        ("len", vec!["Str"], &|v| {
+            vec![Cell::Num(v[0].as_str().chars().count() as f64)]
+        }),
+        ("substr", vec!["Num", "Num", "Str"], &|v| {
+            let s = v[2].as_str();
+            let n = s.chars().count() as i64;
+            let mut start = v[1].as_num().floor() as i64;
+            if start < 0 {
+                start += n;
+            }
+            let len = v[0].as_num().floor().max(0.0) as usize;
+            vec![Cell::Str(
+                s.chars().skip(start.max(0) as usize).take(len).collect(),
+            )]
+        }),
+        ("upper", vec!["Str"], &|v| {
+            vec![Cell::Str(v[0].as_str().to_uppercase())]
+        }),
+        ("lower", vec!["Str"], &|v| {
+            vec![Cell::Str(v[0].as_str().to_lowercase())]
+        }),
+        ("trim", vec!["Str"], &|v| {
+            vec![Cell::Str(v[0].as_str().trim().to_owned())]
+        }),
+        ("split", vec!["Str", "Str"], &|v| {
+            let mut parts: Vec<Cell> = v[1]
+                .as_str()
+                .split(&v[0].as_str())
+                .map(|p| Cell::Str(p.to_owned()))
+                .collect();
+            parts.push(Cell::Num(parts.len() as f64));
+            parts
+        }),
+        ("find", vec!["Str", "Str"], &|v| {
+            let s = v[1].as_str();
+            let i = s
+                .find(&v[0].as_str())
+                .map(|b| s[..b].chars().count() as f64)
+                .unwrap_or(-1.0);
+            vec![Cell::Num(i)]
+        }),
+        ("replace", vec!["Str", "Str", "Str"], &|v| {
+            vec![Cell::Str(
+                v[2].as_str().replace(&v[1].as_str(), &v[0].as_str()),
+            )]
+        }),
+        (
+            "rematch",
+            vec!["Str", "Str"],
+            &|v| match Regex::new(&v[0].as_str()) {
+                Ok(re) => vec![Cell::Bool(re.is_match(&v[1].as_str()))],
+                Err(e) => op_error(&e.to_string(), &v),
+            },
+        ),
+        (
+            "recap",
+            vec!["Str", "Str"],
+            &|v| match Regex::new(&v[0].as_str()) {
+                Ok(re) => {
+                    let s = v[1].as_str();
+                    let mut caps: Vec<Cell> = match re.captures(&s) {
+                        Some(c) if c.len() > 1 => c
+                            .iter()
+                            .skip(1)
+                            .map(|m| Cell::Str(m.map(|m| m.as_str()).unwrap_or("").to_owned()))
+                            .collect(),
+                        Some(c) => vec![Cell::Str(c[0].to_owned())],
+                        None => vec![],
+                    };
+                    caps.push(Cell::Num(caps.len() as f64));
+                    caps
+                }
+                Err(e) => op_error(&e.to_string(), &v),
+            },
+        ),
+        (
+            "str>num",
+            vec!["Str"],
+            &|v| match parse_number(v[0].as_str().trim()) {
+                Some(n) => vec![n],
+                None => op_error(&format!("not a number: {:?}", v[0].as_str()), &v),
+            },
+        ),
+        ("num>str", vec!["Num"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["Word"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["BigInt"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["Rat"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["Decimal"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["Complex"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["Interval"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["Measured"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["SigFig"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ("num>str", vec!["Quantity"], &|v| {
+            vec![Cell::Str(v[0].to_string())]
+        }),
+        ////
+        ("true", vec![], &|_| vec![Cell::Bool(true)]),
+        ("false", vec![], &|_| vec![Cell::Bool(false)]),
+        ("<", vec!["*", "*"], &|v| {
+            cmp_op(&v, |o| o == Some(Ordering::Less))
+        }),
+        ("<=", vec!["*", "*"], &|v| {
+            cmp_op(&v, |o| matches!(o, Some(Ordering::Less | Ordering::Equal)))
+        }),
+        ("==", vec!["*", "*"], &|v| {
+            cmp_op(&v, |o| o == Some(Ordering::Equal))
+        }),
+        ("!=", vec!["*", "*"], &|v| {
+            cmp_op(&v, |o| o != Some(Ordering::Equal))
+        }),
+        (">", vec!["*", "*"], &|v| {
+            cmp_op(&v, |o| o == Some(Ordering::Greater))
+        }),
+        (">=", vec!["*", "*"], &|v| {
+            cmp_op(&v, |o| {
+                matches!(o, Some(Ordering::Greater | Ordering::Equal))
+            })
+        }),
+        ("and", vec!["Bool", "Bool"], &|v| {
+            vec![Cell::Bool(v[1].as_bool() && v[0].as_bool())]
+        }),
+        ("or", vec!["Bool", "Bool"], &|v| {
+            vec![Cell::Bool(v[1].as_bool() || v[0].as_bool())]
+        }),
+        ("xor", vec!["Bool", "Bool"], &|v| {
+            vec![Cell::Bool(v[1].as_bool() != v[0].as_bool())]
+        }),
+        ("not", vec!["Bool"], &|v| vec![Cell::Bool(!v[0].as_bool())]),
+        ////
+        ("list->", vec!["List"], &|v| {
+            let mut l = v[0].as_list();
+            l.push(Cell::Num(l.len() as f64));
+            l
+        }),
+        ("len", vec!["List"], &|v| {
+            vec![Cell::Num(v[0].as_list().len() as f64)]
+        }),
+        ("nth", vec!["Num", "List"], &|v| {
+            let l = v[1].as_list();
+            let mut i = v[0].as_num().floor() as i64;
+            if i < 0 {
+                i += l.len() as i64;
+            }
+            match l.get(i.max(0) as usize) {
+                Some(c) if i >= 0 => vec![c.clone()],
+                _ => op_error(&format!("index {} out of range", v[0].as_num()), &v),
+            }
+        }),
+        ("sort", vec!["List"], &|v| {
+            let mut l = v[0].as_list();
+            let mut err = None;
+            l.sort_by(|a, b| match a.compare(b) {
+                Ok(o) => o.unwrap_or(Ordering::Equal),
+                Err(e) => {
+                    err = Some(e);
+                    Ordering::Equal
+                }
+            });
+            match err {
+                None => vec![Cell::List(l)],
+                Some(e) => op_error(&e, &v),
+            }
+        }),
+        ("reverse", vec!["List"], &|v| {
+            let mut l = v[0].as_list();
+            l.reverse();
+            vec![Cell::List(l)]
+        }),
+        ("sum", vec!["List"], &|v| {
+            let r = nums(&v[0].as_list()).map(|n| vec![Cell::Num(n.iter().sum())]);
+            list_result(r, &v)
+        }),
+        ("prod", vec!["List"], &|v| {
+            let r = nums(&v[0].as_list()).map(|n| vec![Cell::Num(n.iter().product())]);
+            list_result(r, &v)
+        }),
+        ("norm", vec!["List"], &|v| {
+            let r = nums(&v[0].as_list())
+                .map(|n| vec![Cell::Num(n.iter().map(|x| x * x).sum::<f64>().sqrt())]);
+            list_result(r, &v)
+        }),
+        ("dot", vec!["List", "List"], &|v| {
+            let r = nums(&v[1].as_list()).and_then(|a| {
+                let b = nums(&v[0].as_list())?;
+                if a.len() != b.len() {
+                    return Err(format!("dot of lengths {} and {}", a.len(), b.len()));
+                }
+                Ok(vec![Cell::Num(a.iter().zip(b).map(|(x, y)| x * y).sum())])
+            });
+            list_result(r, &v)
+        }),
+        ("cross", vec!["List", "List"], &|v| {
+            let r = nums(&v[1].as_list()).and_then(|a| {
+                let b = nums(&v[0].as_list())?;
+                if a.len() != 3 || b.len() != 3 {
+                    return Err("cross needs two 3-vectors".to_owned());
+                }
+                Ok(vec![Cell::List(vec![
+                    Cell::Num(a[1] * b[2] - a[2] * b[1]),
+                    Cell::Num(a[2] * b[0] - a[0] * b[2]),
+                    Cell::Num(a[0] * b[1] - a[1] * b[0]),
+                ])])
+            });
+            list_result(r, &v)
+        }),
+        ////
+        (">mat", vec!["List"], &|v| {
+            matrix_result(list_to_matrix(&v[0].as_list()), &v)
+        }),
+        ("mat>", vec!["Matrix"], &|v| {
+            let rows = v[0].as_matrix().to_rows();
+            vec![Cell::List(
+                rows.into_iter()
+                    .map(|r| Cell::List(r.into_iter().map(Cell::Num).collect()))
+                    .collect(),
+            )]
+        }),
+        ("identity", vec!["Num"], &|v| {
+            vec![Cell::Matrix(Matrix::identity(
+                v[0].as_num().max(1.0) as usize
+            ))]
+        }),
+        ("zeros", vec!["Num", "Num"], &|v| {
+            vec![Cell::Matrix(Matrix::zeros(
+                v[1].as_num().max(1.0) as usize,
+                v[0].as_num().max(1.0) as usize,
+            ))]
+        }),
+        ("+", vec!["Matrix", "Matrix"], &|v| {
+            matrix_result(v[1].as_matrix().add(&v[0].as_matrix()), &v)
+        }),
+        ("-", vec!["Matrix", "Matrix"], &|v| {
+            matrix_result(v[1].as_matrix().sub(&v[0].as_matrix()), &v)
+        }),
+        ("*", vec!["Matrix", "Matrix"], &|v| {
+            matrix_result(v[1].as_matrix().mul(&v[0].as_matrix()), &v)
+        }),
+        ("*", vec!["Num", "Matrix"], &|v| {
+            vec![Cell::Matrix(v[1].as_matrix().scale(v[0].as_num()))]
+        }),
+        ("*", vec!["Matrix", "Num"], &|v| {
+            vec![Cell::Matrix(v[0].as_matrix().scale(v[1].as_num()))]
+        }),
+        ("*", vec!["List", "Matrix"], &|v| {
+            let r = nums(&v[0].as_list())
+                .and_then(|x| v[1].as_matrix().mul_vec(&x))
+                .map(|y| vec![Cell::List(y.into_iter().map(Cell::Num).collect())]);
+            list_result(r, &v)
+        }),
+        ("transpose", vec!["Matrix"], &|v| {
+            vec![Cell::Matrix(v[0].as_matrix().transpose())]
+        }),
+        ("det", vec!["Matrix"], &|v| {
+            list_result(v[0].as_matrix().det().map(|d| vec![Cell::Num(d)]), &v)
+        }),
+        ("inv", vec!["Matrix"], &|v| {
+            matrix_result(v[0].as_matrix().inverse(), &v)
+        }),
+        ("solve", vec!["Matrix", "Matrix"], &|v| {
+            matrix_result(v[1].as_matrix().solve(&v[0].as_matrix()), &v)
+        }),
+        ("solve", vec!["List", "Matrix"], &|v| {
+            let r = nums(&v[0].as_list())
+                .and_then(|b| {
+                    v[1].as_matrix().solve(&Matrix::from_rows(
+                        b.into_iter().map(|x| vec![x]).collect(),
+                    )?)
+                })
+                .map(|x| {
+                    vec![Cell::List(
+                        x.to_rows().into_iter().map(|r| Cell::Num(r[0])).collect(),
+                    )]
+                });
+            list_result(r, &v)
+        }),
+        ("lu", vec!["Matrix"], &|v| {
+            let r = v[0]
+                .as_matrix()
+                .lu()
+                .map(|(l, u, p)| vec![Cell::Matrix(l), Cell::Matrix(u), Cell::Matrix(p)]);
+            list_result(r, &v)
+        }),
+        ("qr", vec!["Matrix"], &|v| {
+            let (q, r) = v[0].as_matrix().qr();
+            vec![Cell::Matrix(q), Cell::Matrix(r)]
+        }),
+        ("eigsym", vec!["Matrix"], &|v| {
+            let r = v[0]
+                .as_matrix()
+                .eigen_symmetric()
+                .map(|e| vec![Cell::List(e.into_iter().map(Cell::Num).collect())]);
+            list_result(r, &v)
+        }),
+        ////
+        ("complex", vec![], &|_| {
+            settings::update(|s| s.complex = true);
+            vec![]
+        }),
+        ("real", vec![], &|_| {
+            settings::update(|s| s.complex = false);
+            vec![]
+        }),
+        ("polar", vec![], &|_| {
+            settings::update(|s| s.polar = true);
+            vec![]
+        }),
+        ("rect", vec![], &|_| {
+            settings::update(|s| s.polar = false);
+            vec![]
+        }),
+        ("deg", vec![], &|_| {
+            settings::update(|s| s.angle = Angle::Deg);
+            vec![]
+        }),
+        ("rad", vec![], &|_| {
+            settings::update(|s| s.angle = Angle::Rad);
+            vec![]
+        }),
+        ("grad", vec![], &|_| {
+            settings::update(|s| s.angle = Angle::Grad);
+            vec![]
+        }),
+        ("turn", vec![], &|_| {
+            settings::update(|s| s.angle = Angle::Turn);
+            vec![]
+        }),
+        ("bin", vec![], &|_| {
+            settings::update(|s| s.base = 2);
+            vec![]
+        }),
+        ("oct", vec![], &|_| {
+            settings::update(|s| s.base = 8);
+            vec![]
+        }),
+        ("dec", vec![], &|_| {
+            settings::update(|s| s.base = 10);
+            vec![]
+        }),
+        ("hex", vec![], &|_| {
+            settings::update(|s| s.base = 16);
+            vec![]
+        }),
+        (
+            ">big",
+            vec!["Num"],
+            &|v| match BigInt::from_f64(v[0].as_num()) {
+                Some(b) => vec![Cell::BigInt(b)],
+                None => op_error("not an integer", &v),
+            },
+        ),
+        (">big", vec!["Word"], &|v| {
+            vec![Cell::BigInt(BigInt::from_i64(v[0].as_word()))]
+        }),
+        (
+            ">big",
+            vec!["Str"],
+            &|v| match BigInt::parse(&v[0].as_str()) {
+                Some(b) => vec![Cell::BigInt(b)],
+                None => op_error("not an integer", &v),
+            },
+        ),
+        ("big>", vec!["BigInt"], &|v| {
+            vec![Cell::Num(v[0].as_bigint().to_f64())]
+        }),
+        ("+", vec!["BigInt", "BigInt"], &|v| {
+            vec![Cell::BigInt(&v[1].as_bigint() + &v[0].as_bigint())]
+        }),
+        ("-", vec!["BigInt", "BigInt"], &|v| {
+            vec![Cell::BigInt(&v[1].as_bigint() - &v[0].as_bigint())]
+        }),
+        ("*", vec!["BigInt", "BigInt"], &|v| {
+            vec![Cell::BigInt(&v[1].as_bigint() * &v[0].as_bigint())]
+        }),
+        (
+            "/",
+            vec!["BigInt", "BigInt"],
+            &|v| match v[1].as_bigint().div_rem(&v[0].as_bigint()) {
+                Ok((q, _)) => vec![Cell::BigInt(q)],
+                Err(e) => op_error(&e, &v),
+            },
+        ),
+        (
+            "mod",
+            vec!["BigInt", "BigInt"],
+            &|v| match v[1].as_bigint().div_rem(&v[0].as_bigint()) {
+                Ok((_, r)) => vec![Cell::BigInt(r)],
+                Err(e) => op_error(&e, &v),
+            },
+        ),
+        (
+            "pow",
+            vec!["BigInt", "BigInt"],
+            &|v| match v[0].as_bigint().to_u32() {
+                Some(e) => match v[1].as_bigint().pow(e) {
+                    Ok(p) => vec![Cell::BigInt(p)],
+                    Err(e) => op_error(&e, &v),
+                },
+                None => op_error("pow needs a small non-negative exponent", &v),
+            },
+        ),
+        ("abs", vec!["BigInt"], &|v| {
+            vec![Cell::BigInt(v[0].as_bigint().abs())]
+        }),
+        ("gcd", vec!["BigInt", "BigInt"], &|v| {
+            vec![Cell::BigInt(v[1].as_bigint().gcd(&v[0].as_bigint()))]
+        }),
+        ("lcm", vec!["BigInt", "BigInt"], &|v| {
+            vec![Cell::BigInt(v[1].as_bigint().lcm(&v[0].as_bigint()))]
+        }),
+        (
+            "fact",
+            vec!["BigInt"],
+            &|v| match v[0].as_bigint().factorial() {
+                Ok(f) => vec![Cell::BigInt(f)],
+                Err(e) => op_error(&e, &v),
+            },
+        ),
+        (
+            "modpow",
+            vec!["BigInt", "BigInt", "BigInt"],
+            &|v| match v[2]
+                .as_bigint()
+                .modpow(&v[1].as_bigint(), &v[0].as_bigint())
+            {
+                Ok(r) => vec![Cell::BigInt(r)],
+                Err(e) => op_error(&e, &v),
+            },
+        ),
+        (
+            ">rat",
+            vec!["Num"],
+            &|v| match Rational::from_f64(v[0].as_num()) {
+                Some(r) => vec![Cell::Rat(r)],
+                None => op_error("no rational that close fits", &v),
+            },
+        ),
+        ("rat>", vec!["Rat"], &|v| {
+            vec![Cell::Num(v[0].as_rat().as_f64())]
+        }),
+        (
+            "approx",
+            vec!["Num", "Num"],
+            &|v| match Rational::approximate(v[1].as_num(), v[0].as_num() as i64) {
+                Some(r) => vec![Cell::Rat(r)],
+                None => op_error("no rational that close fits", &v),
+            },
+        ),
+        ("numer", vec!["Rat"], &|v| {
+            vec![Cell::Num(v[0].as_rat().numerator() as f64)]
+        }),
+        ("denom", vec!["Rat"], &|v| {
+            vec![Cell::Num(v[0].as_rat().denominator() as f64)]
+        }),
+        ("+", vec!["Rat", "Rat"], &|v| {
+            rat_result(v[1].as_rat().checked_add(v[0].as_rat()), &v)
+        }),
+        ("-", vec!["Rat", "Rat"], &|v| {
+            rat_result(v[1].as_rat().checked_sub(v[0].as_rat()), &v)
+        }),
+        ("*", vec!["Rat", "Rat"], &|v| {
+            rat_result(v[1].as_rat().checked_mul(v[0].as_rat()), &v)
+        }),
+        ("/", vec!["Rat", "Rat"], &|v| {
+            rat_result(v[1].as_rat().checked_div(v[0].as_rat()), &v)
+        }),
+        ("pow", vec!["Rat", "Rat"], &|v| {
+            let e = v[0].as_rat();
+            match i32::try_from(e.numerator()) {
+                Ok(n) if e.is_integer() => rat_result(v[1].as_rat().checked_pow(n), &v),
+                _ => op_error("pow needs an integer exponent", &v),
+            }
+        }),
+        ("1/", vec!["Rat"], &|v| {
+            rat_result(Rational::from_int(1).checked_div(v[0].as_rat()), &v)
+        }),
+        ("recip", vec!["Rat"], &|v| {
+            rat_result(Rational::from_int(1).checked_div(v[0].as_rat()), &v)
+        }),
+        ("abs", vec!["Rat"], &|v| {
+            rat_result(v[0].as_rat().checked_abs(), &v)
+        }),
+        ("halfeven", vec![], &|_| {
+            settings::update(|s| s.rounding = Rounding::HalfEven);
+            vec![]
+        }),
+        ("halfup", vec![], &|_| {
+            settings::update(|s| s.rounding = Rounding::HalfUp);
+            vec![]
+        }),
+        ("truncate", vec![], &|_| {
+            settings::update(|s| s.rounding = Rounding::Truncate);
+            vec![]
+        }),
+        ("places", vec!["Num"], &|v| {
+            let n = v[0].as_num();
+            if n.fract() != 0.0 || !(0.0..=30.0).contains(&n) {
+                return op_error("places must be a whole number from 0 to 30", &v);
+            }
+            settings::update(|s| s.places = Some(n as u32));
+            vec![]
+        }),
+        ("noplaces", vec![], &|_| {
+            settings::update(|s| s.places = None);
+            vec![]
+        }),
+        (
+            ">decimal",
+            vec!["Num"],
+            &|v| match Decimal::from_f64(v[0].as_num()) {
+                Some(d) => vec![Cell::Decimal(d)],
+                None => op_error("too big for a decimal", &v),
+            },
+        ),
+        ("decimal>", vec!["Decimal"], &|v| {
+            vec![Cell::Num(v[0].as_decimal().to_f64())]
+        }),
+        ("+", vec!["Decimal", "Decimal"], &|v| {
+            dec_result(v[1].as_decimal().checked_add(v[0].as_decimal()), &v)
+        }),
+        ("-", vec!["Decimal", "Decimal"], &|v| {
+            dec_result(v[1].as_decimal().checked_sub(v[0].as_decimal()), &v)
+        }),
+        ("*", vec!["Decimal", "Decimal"], &|v| {
+            dec_result(v[1].as_decimal().checked_mul(v[0].as_decimal()), &v)
+        }),
+        ("/", vec!["Decimal", "Decimal"], &|v| {
+            dec_result(dec_div(v[1].as_decimal(), v[0].as_decimal()), &v)
+        }),
+        ("pow", vec!["Decimal", "Decimal"], &|v| {
+            let e = v[0].as_decimal();
+            match u32::try_from(e.to_f64() as i64) {
+                Ok(n) if e.is_integer() => dec_result(v[1].as_decimal().checked_pow(n), &v),
+                _ => op_error("pow needs a non-negative integer exponent", &v),
+            }
+        }),
+        ("abs", vec!["Decimal"], &|v| {
+            vec![Cell::Decimal(v[0].as_decimal().abs())]
+        }),
+        ("round", vec!["Num", "Decimal"], &|v| {
+            let n = v[0].as_num();
+            if n.fract() != 0.0 || n < 0.0 {
+                return op_error("round needs a whole number of places", &v);
+            }
+            let rounding = settings::get().rounding;
+            vec![Cell::Decimal(v[1].as_decimal().round(n as u32, rounding))]
+        }),
+        ("%", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(v[1].as_num() * v[0].as_num() / 100.0)]
+        }),
+        ("%ch", vec!["Num", "Num"], &|v| {
+            let old = v[1].as_num();
+            vec![Cell::Num((v[0].as_num() - old) / old * 100.0)]
+        }),
+        ("markup", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(v[1].as_num() * (1.0 + v[0].as_num() / 100.0))]
+        }),
+        ("margin", vec!["Num", "Num"], &|v| {
+            vec![Cell::Num(v[1].as_num() / (1.0 - v[0].as_num() / 100.0))]
+        }),
+        ("%", vec!["Decimal", "Decimal"], &|v| {
+            let r = v[1].as_decimal().checked_mul(v[0].as_decimal().percent());
+            dec_result(r, &v)
+        }),
+        ("%ch", vec!["Decimal", "Decimal"], &|v| {
+            let old = v[1].as_decimal();
+            let r = v[0]
+                .as_decimal()
+                .checked_sub(old)
+                .and_then(|d| d.checked_mul(Decimal::new(100, 0)))
+                .and_then(|d| dec_div(d, old));
+            dec_result(r, &v)
+        }),
+        ("markup", vec!["Decimal", "Decimal"], &|v| {
+            let r = Decimal::new(1, 0)
+                .checked_add(v[0].as_decimal().percent())
+                .and_then(|f| v[1].as_decimal().checked_mul(f));
+            dec_result(r, &v)
+        }),
+        ("margin", vec!["Decimal", "Decimal"], &|v| {
+            let r = Decimal::new(1, 0)
+                .checked_sub(v[0].as_decimal().percent())
+                .and_then(|f| dec_div(v[1].as_decimal(), f));
+            dec_result(r, &v)
+        }),
+        ("interval", vec!["Num", "Num"], &|v| {
+            iv_result(Interval::checked(v[1].as_num(), v[0].as_num()), &v)
+        }),
+        ("±", vec!["Num", "Num"], &|v| {
+            iv_result(Interval::around(v[1].as_num(), v[0].as_num()), &v)
+        }),
+        ("bounds", vec!["Interval"], &|v| {
+            let i = v[0].as_interval();
+            vec![Cell::Num(i.lo), Cell::Num(i.hi)]
+        }),
+        ("width", vec!["Interval"], &|v| {
+            vec![Cell::Num(v[0].as_interval().width())]
+        }),
+        ("mid", vec!["Interval"], &|v| {
+            vec![Cell::Num(v[0].as_interval().mid())]
+        }),
+        ("contains", vec!["Num", "Interval"], &|v| {
+            vec![Cell::Bool(v[1].as_interval().contains(v[0].as_num()))]
+        }),
+        ("+", vec!["Interval", "Interval"], &|v| {
+            vec![Cell::Interval(v[1].as_interval().add(v[0].as_interval()))]
+        }),
+        ("-", vec!["Interval", "Interval"], &|v| {
+            vec![Cell::Interval(v[1].as_interval().sub(v[0].as_interval()))]
+        }),
+        ("*", vec!["Interval", "Interval"], &|v| {
+            vec![Cell::Interval(v[1].as_interval().mul(v[0].as_interval()))]
+        }),
+        ("/", vec!["Interval", "Interval"], &|v| {
+            iv_result(v[1].as_interval().div(v[0].as_interval()), &v)
         }),
-        ("weeks", vec!["Num"], &|v| {
-            vec![Cell::Days(7 * v[0].as_num().floor() as i64)]
+        ("pow", vec!["Interval", "Interval"], &|v| {
+            iv_result(v[1].as_interval().pow(v[0].as_interval()), &v)
         }),
-        ("+", vec!["Days", "Date"], &|v| {
-            vec![Cell::Date(v[1].as_date().add_days(v[0].as_days() as u16))]
+        ("atan2", vec!["Interval", "Interval"], &|v| {
+            let a = v[1].as_interval().atan2(v[0].as_interval());
+            vec![Cell::Interval(iv_scale(a, from_radians(1.0)))]
         }),
-        ("dow", vec!["Date"], &|a| {
-            vec![Cell::Str(format!("{:?}", a[0].as_date().dow()))]
+        ("d2r", vec!["Interval"], &|v| {
+            vec![Cell::Interval(iv_scale(
+                v[0].as_interval(),
+                1f64.to_radians(),
+            ))]
         }),
-        ("doy", vec!["Date"], &|a| {
-            vec![Cell::Num(a[0].as_date().doy() as f64)]
+        ("r2d", vec!["Interval"], &|v| {
+            vec![Cell::Interval(iv_scale(
+                v[0].as_interval(),
+                1f64.to_degrees(),
+            ))]
         }),
-        ("+", vec!["Num", "Num"], &|v| {
-            vec![Cell::Num(v[1].as_num() + v[0].as_num())]
+        ("hypot", vec!["Interval", "Interval"], &|v| {
+            let sum = v[1].as_interval().sq().add(v[0].as_interval().sq());
+            iv_result(sum.sqrt(), &v)
         }),
-        ("-", vec!["Num", "Num"], &|v| {
-            vec![Cell::Num(v[1].as_num() - v[0].as_num())]
+        ("sin", vec!["Interval"], &|v| {
+            vec![Cell::Interval(
+                iv_scale(v[0].as_interval(), to_radians(1.0)).sin(),
+            )]
         }),
-        ("*", vec!["Num", "Num"], &|v| {
-            vec![Cell::Num(v[1].as_num() * v[0].as_num())]
+        ("cos", vec!["Interval"], &|v| {
+            vec![Cell::Interval(
+                iv_scale(v[0].as_interval(), to_radians(1.0)).cos(),
+            )]
         }),
-        ("/", vec!["Num", "Num"], &|v| {
-            vec![Cell::Num(v[1].as_num() / v[0].as_num())]
+        ("tan", vec!["Interval"], &|v| {
+            iv_result(iv_scale(v[0].as_interval(), to_radians(1.0)).tan(), &v)
         }),
-        ("pow", vec!["Num", "Num"], &|v| {
-            vec![Cell::Num(f64::powf(v[1].as_num(), v[0].as_num()))]
+        ("asin", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::asin, (-1.0, 1.0), "asin")
+                    .map(|i| iv_scale(i, from_radians(1.0))),
+                &v,
+            )
         }),
-        ("atan2", vec!["Num", "Num"], &|v| {
-            vec![Cell::Num(f64::atan2(v[1].as_num(), v[0].as_num()))]
+        ("acos", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .decreasing(f64::acos, (-1.0, 1.0), "acos")
+                    .map(|i| iv_scale(i, from_radians(1.0))),
+                &v,
+            )
         }),
-        ("hypot", vec!["Num", "Num"], &|v| {
-            vec![Cell::Num(f64::hypot(v[1].as_num(), v[0].as_num()))]
+        ("atan", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::atan, (f64::NEG_INFINITY, f64::INFINITY), "atan")
+                    .map(|i| iv_scale(i, from_radians(1.0))),
+                &v,
+            )
         }),
-        ("sin", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::sin(v[0].as_num()))]
+        ("sinh", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval().increasing(
+                    f64::sinh,
+                    (f64::NEG_INFINITY, f64::INFINITY),
+                    "sinh",
+                ),
+                &v,
+            )
         }),
-        ("cos", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::cos(v[0].as_num()))]
+        ("tanh", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval().increasing(
+                    f64::tanh,
+                    (f64::NEG_INFINITY, f64::INFINITY),
+                    "tanh",
+                ),
+                &v,
+            )
         }),
-        ("tan", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::tan(v[0].as_num()))]
+        ("asinh", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval().increasing(
+                    f64::asinh,
+                    (f64::NEG_INFINITY, f64::INFINITY),
+                    "asinh",
+                ),
+                &v,
+            )
         }),
-        ("asin", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::asin(v[0].as_num()))]
+        ("acosh", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::acosh, (1.0, f64::INFINITY), "acosh"),
+                &v,
+            )
         }),
-        ("acos", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::acos(v[0].as_num()))]
+        ("atanh", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::atanh, (-1.0, 1.0), "atanh"),
+                &v,
+            )
         }),
-        ("atan", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::atan(v[0].as_num()))]
+        ("ln", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::ln, (0.0, f64::INFINITY), "ln"),
+                &v,
+            )
         }),
-        ("sinh", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::sinh(v[0].as_num()))]
+        ("ln1+", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::ln_1p, (-1.0, f64::INFINITY), "ln1+"),
+                &v,
+            )
         }),
-        ("cosh", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::cosh(v[0].as_num()))]
+        ("log10", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::log10, (0.0, f64::INFINITY), "log10"),
+                &v,
+            )
         }),
-        ("tanh", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::tanh(v[0].as_num()))]
+        ("log2", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::log2, (0.0, f64::INFINITY), "log2"),
+                &v,
+            )
         }),
-        ("asinh", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::asinh(v[0].as_num()))]
+        ("exp", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval()
+                    .increasing(f64::exp, (f64::NEG_INFINITY, f64::INFINITY), "exp"),
+                &v,
+            )
         }),
-        ("acosh", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::acosh(v[0].as_num()))]
+        ("exp-1", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval().increasing(
+                    f64::exp_m1,
+                    (f64::NEG_INFINITY, f64::INFINITY),
+                    "exp-1",
+                ),
+                &v,
+            )
         }),
-        ("atanh", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::atanh(v[0].as_num()))]
+        ("cbrt", vec!["Interval"], &|v| {
+            iv_result(
+                v[0].as_interval().increasing(
+                    f64::cbrt,
+                    (f64::NEG_INFINITY, f64::INFINITY),
+                    "cbrt",
+                ),
+                &v,
+            )
         }),
-        ////
-        ("d2r", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::to_radians(v[0].as_num()))]
+        ("cosh", vec!["Interval"], &|v| {
+            vec![Cell::Interval(v[0].as_interval().cosh())]
         }),
-        ("r2d", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::to_degrees(v[0].as_num()))]
+        ("sqrt", vec!["Interval"], &|v| {
+            iv_result(v[0].as_interval().sqrt(), &v)
         }),
-        ////
-        ("1/", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::recip(v[0].as_num()))]
+        ("1/", vec!["Interval"], &|v| {
+            iv_result(v[0].as_interval().recip(), &v)
         }),
-        ("recip", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::recip(v[0].as_num()))]
+        ("recip", vec!["Interval"], &|v| {
+            iv_result(v[0].as_interval().recip(), &v)
         }),
-        ////
-        ("ln", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::ln(v[0].as_num()))]
+        ("sq", vec!["Interval"], &|v| {
+            vec![Cell::Interval(v[0].as_interval().sq())]
         }),
-        ("ln1+", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::ln_1p(v[0].as_num()))]
+        ("cb", vec!["Interval"], &|v| {
+            iv_result(v[0].as_interval().powf(3.0), &v)
         }),
-        ("log10", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::log10(v[0].as_num()))]
+        ("abs", vec!["Interval"], &|v| {
+            vec![Cell::Interval(v[0].as_interval().abs())]
         }),
-        ("log2", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::log2(v[0].as_num()))]
+        ("ceil", vec!["Interval"], &|v| {
+            let i = v[0].as_interval();
+            vec![Cell::Interval(Interval::new(i.lo.ceil(), i.hi.ceil()))]
         }),
-        ("exp", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::exp(v[0].as_num()))]
+        ("floor", vec!["Interval"], &|v| {
+            let i = v[0].as_interval();
+            vec![Cell::Interval(Interval::new(i.lo.floor(), i.hi.floor()))]
         }),
-        ("exp-1", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::exp_m1(v[0].as_num()))]
+        ("meas", vec!["Num", "Num"], &|v| {
+            ms(Measured::new(v[1].as_num(), v[0].as_num()))
         }),
-        ("sqrt", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::sqrt(v[0].as_num()))]
+        ("value", vec!["Measured"], &|v| {
+            vec![Cell::Num(v[0].as_measured().value)]
         }),
-        ("cbrt", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::cbrt(v[0].as_num()))]
+        ("sigma", vec!["Measured"], &|v| {
+            vec![Cell::Num(v[0].as_measured().sigma())]
         }),
-        ("sq", vec!["Num"], &|a| {
-            vec![Cell::Num(a[0].as_num() * a[0].as_num())]
+        ("+", vec!["Measured", "Measured"], &|v| {
+            ms(v[1].as_measured().add(&v[0].as_measured()))
         }),
-        ("cb", vec!["Num"], &|a| {
-            vec![Cell::Num(a[0].as_num() * a[0].as_num() * a[0].as_num())]
+        ("-", vec!["Measured", "Measured"], &|v| {
+            ms(v[1].as_measured().sub(&v[0].as_measured()))
         }),
-        ////
-        ("abs", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::abs(v[0].as_num()))]
+        ("*", vec!["Measured", "Measured"], &|v| {
+            ms(v[1].as_measured().mul(&v[0].as_measured()))
         }),
-        ("ceil", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::ceil(v[0].as_num()))]
+        ("/", vec!["Measured", "Measured"], &|v| {
+            ms(v[1].as_measured().div(&v[0].as_measured()))
         }),
-        ("floor", vec!["Num"], &|v| {
-            vec![Cell::Num(f64::floor(v[0].as_num()))]
+        ("pow", vec!["Measured", "Measured"], &|v| {
+            ms(v[1].as_measured().pow(&v[0].as_measured()))
         }),
-        ("pi", vec![], &|_| vec![Cell::Num(PI)]),
-        ("e", vec![], &|_| vec![Cell::Num(E)]),
-        ("sqrt2", vec![], &|_| vec![Cell::Num(SQRT_2)]),
-        ("ln2", vec![], &|_| vec![Cell::Num(LN_2)]),
-        ("ln10", vec![], &|_| vec![Cell::Num(LN_10)]),
-        ("tau", vec![], &|_| vec![Cell::Num(TAU)]),
-        ("pi/2", vec![], &|_| vec![Cell::Num(FRAC_PI_2)]),
-        ("pi/4", vec![], &|_| vec![Cell::Num(FRAC_PI_4)]),
-        ("drop", vec!["*"], &|_v| vec![]),
-        ("swap", vec!["*", "*"], &|v| {
-            vec![v[0].clone(), v[1].clone()]
+        ("hypot", vec!["Measured", "Measured"], &|v| {
+            ms(v[1].as_measured().hypot(&v[0].as_measured()))
         }),
-        ("rot", vec!["*", "*", "*"], &|v| {
-            vec![v[0].clone(), v[2].clone(), v[1].clone()]
+        ("atan2", vec!["Measured", "Measured"], &|v| {
+            ms(v[1]
+                .as_measured()
+                .atan2(&v[0].as_measured())
+                .scale(from_radians(1.0)))
         }),
-        ("dup", vec!["*"], &|v| vec![v[0].clone(), v[0].clone()]),
-        ////
-        ("w", vec!["Num"], &|v| {
-            if let Cell::Num(n) = v[0] {
-                vec![Cell::Word(n as i64)]
-            } else {
-                let v = &v[0];
-                println!("not a number: {v:?}; cannot convert to word");
-                vec![]
-            }
+        ("sin", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .scale(to_radians(1.0))
+                .map(f64::sin, f64::cos))
         }),
-        ////
-        ("dms2dd", vec!["Num", "Num", "Num"], &|v| {
-            if let Cell::Num(d) = v[2] {
-                if let Cell::Num(m) = v[1] {
-                    if let Cell::Num(s) = v[0] {
-                        vec![Cell::Num(d + m / 60.0 + s / 3600.0)]
-                    } else {
-                        let s = &v[0];
-                        println!("s was not a number: {s:?}");
-                        vec![]
-                    }
-                } else {
-                    let m = &v[1];
-                    println!("m was not a number: {m:?}");
-                    vec![]
-                }
-            } else {
-                let d = &v[2];
-                println!("d was not a number: {d:?}");
-                vec![]
+        ("cos", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .scale(to_radians(1.0))
+                .map(f64::cos, |x| -x.sin()))
+        }),
+        ("tan", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .scale(to_radians(1.0))
+                .map(f64::tan, |x| 1.0 / (x.cos() * x.cos())))
+        }),
+        ("asin", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .map(f64::asin, |x| 1.0 / (1.0 - x * x).sqrt())
+                .scale(from_radians(1.0)))
+        }),
+        ("acos", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .map(f64::acos, |x| -1.0 / (1.0 - x * x).sqrt())
+                .scale(from_radians(1.0)))
+        }),
+        ("atan", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .map(f64::atan, |x| 1.0 / (1.0 + x * x))
+                .scale(from_radians(1.0)))
+        }),
+        ("sinh", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::sinh, f64::cosh))
+        }),
+        ("cosh", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::cosh, f64::sinh))
+        }),
+        ("tanh", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .map(f64::tanh, |x| 1.0 - x.tanh() * x.tanh()))
+        }),
+        ("asinh", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .map(f64::asinh, |x| 1.0 / (x * x + 1.0).sqrt()))
+        }),
+        ("acosh", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .map(f64::acosh, |x| 1.0 / (x * x - 1.0).sqrt()))
+        }),
+        ("atanh", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::atanh, |x| 1.0 / (1.0 - x * x)))
+        }),
+        ("1/", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::recip, |x| -1.0 / (x * x)))
+        }),
+        ("recip", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::recip, |x| -1.0 / (x * x)))
+        }),
+        ("ln", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::ln, f64::recip))
+        }),
+        ("ln1+", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::ln_1p, |x| 1.0 / (1.0 + x)))
+        }),
+        ("log10", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::log10, |x| 1.0 / (x * LN_10)))
+        }),
+        ("log2", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::log2, |x| 1.0 / (x * LN_2)))
+        }),
+        ("exp", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::exp, f64::exp))
+        }),
+        ("exp-1", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::exp_m1, f64::exp))
+        }),
+        ("sqrt", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::sqrt, |x| 0.5 / x.sqrt()))
+        }),
+        ("cbrt", vec!["Measured"], &|v| {
+            ms(v[0]
+                .as_measured()
+                .map(f64::cbrt, |x| 1.0 / (3.0 * x.cbrt() * x.cbrt())))
+        }),
+        ("sq", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(|x| x * x, |x| 2.0 * x))
+        }),
+        ("cb", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(|x| x * x * x, |x| 3.0 * x * x))
+        }),
+        ("abs", vec!["Measured"], &|v| {
+            ms(v[0].as_measured().map(f64::abs, f64::signum))
+        }),
+        ("ukeep", vec![], &|_| {
+            settings::update(|s| s.units = UnitStyle::Keep);
+            vec![]
+        }),
+        ("ubase", vec![], &|_| {
+            settings::update(|s| s.units = UnitStyle::Base);
+            vec![]
+        }),
+        ("usi", vec![], &|_| {
+            settings::update(|s| s.units = UnitStyle::Si);
+            vec![]
+        }),
+        ("uprefer", vec!["Str"], &|v| {
+            let units = v[0]
+                .as_str()
+                .split_whitespace()
+                .map(str::to_owned)
+                .collect();
+            settings::update(|s| s.units = UnitStyle::Prefer(units));
+            vec![]
+        }),
+        ("sigfig", vec![], &|_| {
+            settings::update(|s| s.sigfigs = true);
+            vec![]
+        }),
+        ("nosigfig", vec![], &|_| {
+            settings::update(|s| s.sigfigs = false);
+            vec![]
+        }),
+        (">sig", vec!["Num", "Num"], &|v| {
+            let n = v[0].as_num();
+            if n.fract() != 0.0 || !(1.0..=17.0).contains(&n) {
+                return op_error("sig-fig count must be a whole number from 1 to 17", &v);
             }
+            sf(SigFig::new(v[1].as_num(), n as u32))
         }),
-        ("dd2dms", vec!["Num"], &|v| {
-            if let Cell::Num(mut v) = v[0] {
-                let d = v.floor();
-                v = (v - d) * 60.0;
-                let m = v.floor();
-                v = (v - m) * 60.0;
-                let s = v;
-                vec![Cell::Num(d), Cell::Num(m), Cell::Num(s)]
-            } else {
-                let d = &v[0];
-                println!("d was not a number: {d:?}");
-                vec![]
+        ("sig>", vec!["SigFig"], &|v| {
+            vec![Cell::Num(v[0].as_sigfig().value)]
+        }),
+        ("sigfigs", vec!["SigFig"], &|v| {
+            let n = v[0]
+                .as_sigfig()
+                .digits()
+                .map_or(f64::INFINITY, |d| d as f64);
+            vec![Cell::Num(n)]
+        }),
+        ("+", vec!["SigFig", "SigFig"], &|v| {
+            sf(v[1].as_sigfig().add(&v[0].as_sigfig()))
+        }),
+        ("-", vec!["SigFig", "SigFig"], &|v| {
+            sf(v[1].as_sigfig().sub(&v[0].as_sigfig()))
+        }),
+        ("*", vec!["SigFig", "SigFig"], &|v| {
+            sf(v[1].as_sigfig().mul(&v[0].as_sigfig()))
+        }),
+        ("/", vec!["SigFig", "SigFig"], &|v| {
+            sf(v[1].as_sigfig().div(&v[0].as_sigfig()))
+        }),
+        // The exponent is taken as exact, as it usually is, e.g. a square.
+        ("pow", vec!["SigFig", "SigFig"], &|v| {
+            let e = v[0].as_sigfig().value;
+            sf(v[1].as_sigfig().map(|x| x.powf(e)))
+        }),
+        ("sqrt", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().map(f64::sqrt))
+        }),
+        ("cbrt", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().map(f64::cbrt))
+        }),
+        ("sq", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().map(|x| x * x))
+        }),
+        ("cb", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().map(|x| x * x * x))
+        }),
+        ("1/", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().map(f64::recip))
+        }),
+        ("recip", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().map(f64::recip))
+        }),
+        ("abs", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().map(f64::abs))
+        }),
+        ("ln", vec!["SigFig"], &|v| sf(v[0].as_sigfig().log(f64::ln))),
+        ("log10", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().log(f64::log10))
+        }),
+        ("log2", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().log(f64::log2))
+        }),
+        ("exp", vec!["SigFig"], &|v| {
+            sf(v[0].as_sigfig().exp(f64::exp))
+        }),
+        ("+", vec!["Quantity", "Quantity"], &|v| {
+            qty_result(v[1].as_quantity().add(&v[0].as_quantity()), &v)
+        }),
+        ("-", vec!["Quantity", "Quantity"], &|v| {
+            qty_result(v[1].as_quantity().sub(&v[0].as_quantity()), &v)
+        }),
+        ("*", vec!["Quantity", "Quantity"], &|v| {
+            qty_result(v[1].as_quantity().mul(&v[0].as_quantity()), &v)
+        }),
+        ("/", vec!["Quantity", "Quantity"], &|v| {
+            qty_result(v[1].as_quantity().div(&v[0].as_quantity()), &v)
+        }),
+        ("pow", vec!["Num", "Quantity"], &|v| {
+            let n = v[0].as_num();
+            if n.fract() != 0.0 || n.abs() > 64.0 {
+                return op_error("a quantity's power must be a small whole number", &v);
             }
+            qty_result(v[1].as_quantity().powi(n as i32), &v)
         }),
-        ("+", vec!["Num", "Num"], &|v| {
-            vec![Cell::Word(i64::wrapping_add(
-                v[1].as_word(),
-                v[0].as_word(),
-            ))]
+        ("sq", vec!["Quantity"], &|v| {
+            qty_result(v[0].as_quantity().powi(2), &v)
         }),
-        ("-", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(v[1].as_word().wrapping_sub(v[0].as_word()))]
+        ("1/", vec!["Quantity"], &|v| {
+            qty_result(Quantity::number(1.0).div(&v[0].as_quantity()), &v)
         }),
-        ("*", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(i64::wrapping_mul(
-                v[1].as_word(),
-                v[0].as_word(),
-            ))]
+        ("recip", vec!["Quantity"], &|v| {
+            qty_result(Quantity::number(1.0).div(&v[0].as_quantity()), &v)
         }),
-        ("/", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(v[1].as_word() / v[0].as_word())]
+        ("abs", vec!["Quantity"], &|v| {
+            vec![Cell::Quantity(v[0].as_quantity().abs())]
         }),
-        ("mod", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(v[1].as_word() % v[0].as_word())]
+        ("value", vec!["Quantity"], &|v| {
+            vec![Cell::Num(v[0].as_quantity().value)]
         }),
-        ("&", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(i64::bitand(v[1].as_word(), v[0].as_word()))]
+        ("unit", vec!["Quantity"], &|v| {
+            vec![Cell::Str(v[0].as_quantity().unit.to_string())]
         }),
-        ("^", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(i64::bitxor(v[1].as_word(), v[0].as_word()))]
+        ("cx", vec!["Num", "Num"], &|v| {
+            cx(Complex::new(v[1].as_num(), v[0].as_num()))
         }),
-        ("|", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(i64::bitor(v[1].as_word(), v[0].as_word()))]
+        ("pol", vec!["Num", "Num"], &|v| {
+            cx(Complex::from_polar(
+                v[1].as_num(),
+                to_radians(v[0].as_num()),
+            ))
         }),
-        ("sl", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(v[1].as_word() << v[0].as_word())]
+        ("re", vec!["Complex"], &|v| {
+            vec![Cell::Num(v[0].as_complex().re)]
         }),
-        ("asr", vec!["Word", "Word"], &|v| {
-            vec![Cell::Word(v[1].as_word() >> v[0].as_word())]
+        ("im", vec!["Complex"], &|v| {
+            vec![Cell::Num(v[0].as_complex().im)]
         }),
-        ("lsr", vec!["Num", "Num"], &|v| {
-            vec![Cell::Word(i64::from_be_bytes(
-                ((u64::from_be_bytes(v[1].as_word().to_be_bytes())) >> v[0].as_word())
-                    .to_be_bytes(),
-            ))]
+        ("abs", vec!["Complex"], &|v| {
+            vec![Cell::Num(v[0].as_complex().abs())]
         }),
-        ("~", vec!["Word"], &|v| vec![Cell::Word(!v[0].as_word())]),
-    ];
+        ("arg", vec!["Complex"], &|v| {
+            vec![Cell::Num(from_radians(v[0].as_complex().arg()))]
+        }),
+        ("conj", vec!["Complex"], &|v| cx(v[0].as_complex().conj())),
+        ("+", vec!["Complex", "Complex"], &|v| {
+            cx(v[1].as_complex() + v[0].as_complex())
+        }),
+        ("-", vec!["Complex", "Complex"], &|v| {
+            cx(v[1].as_complex() - v[0].as_complex())
+        }),
+        ("*", vec!["Complex", "Complex"], &|v| {
+            cx(v[1].as_complex() * v[0].as_complex())
+        }),
+        ("/", vec!["Complex", "Complex"], &|v| {
+            cx(v[1].as_complex() / v[0].as_complex())
+        }),
+        ("pow", vec!["Complex", "Complex"], &|v| {
+            cx(v[1].as_complex().pow(v[0].as_complex()))
+        }),
+        ("sq", vec!["Complex"], &|v| {
+            cx(v[0].as_complex() * v[0].as_complex())
+        }),
+        ("1/", vec!["Complex"], &|v| cx(v[0].as_complex().recip())),
+        ("recip", vec!["Complex"], &|v| cx(v[0].as_complex().recip())),
+        ("sqrt", vec!["Complex"], &|v| cx(v[0].as_complex().sqrt())),
+        ("exp", vec!["Complex"], &|v| cx(v[0].as_complex().exp())),
+        ("ln", vec!["Complex"], &|v| cx(v[0].as_complex().ln())),
+        ("log10", vec!["Complex"], &|v| cx(v[0].as_complex().log10())),
+        ("log2", vec!["Complex"], &|v| cx(v[0].as_complex().log2())),
+        ("sin", vec!["Complex"], &|v| {
+            cx(cx_to_radians(v[0].as_complex()).sin())
+        }),
+        ("cos", vec!["Complex"], &|v| {
+            cx(cx_to_radians(v[0].as_complex()).cos())
+        }),
+        ("tan", vec!["Complex"], &|v| {
+            cx(cx_to_radians(v[0].as_complex()).tan())
+        }),
+        ("asin", vec!["Complex"], &|v| {
+            cx(cx_from_radians(v[0].as_complex().asin()))
+        }),
+        ("acos", vec!["Complex"], &|v| {
+            cx(cx_from_radians(v[0].as_complex().acos()))
+        }),
+        ("atan", vec!["Complex"], &|v| {
+            cx(cx_from_radians(v[0].as_complex().atan()))
+        }),
+        ("sinh", vec!["Complex"], &|v| cx(v[0].as_complex().sinh())),
+        ("cosh", vec!["Complex"], &|v| cx(v[0].as_complex().cosh())),
+        ("tanh", vec!["Complex"], &|v| cx(v[0].as_complex().tanh())),
+        ("asinh", vec!["Complex"], &|v| cx(v[0].as_complex().asinh())),
+        ("acosh", vec!["Complex"], &|v| cx(v[0].as_complex().acosh())),
+        ("atanh", vec!["Complex"], &|v| cx(v[0].as_complex().atanh())),
+    ]
+}
+
+impl Calc<'static> {
+    fn new(units: Units) -> Self {
+        Calc {
+            ops: ops(),
+            units,
+            date_pattern: Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap(),
+            stack: vec![],
+        }
+    }
+}
 
-    let mut stack = vec![];
+fn main() -> io::Result<()> {
+    let mut calc = Calc::new(load_units());
+    settings::load();
     let stdin = io::stdin();
-    let date_pattern = Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap();
-    let units = load_units();
     loop {
+        let before = settings::get();
         let mut buffer = String::new();
-        stdin.read_line(&mut buffer)?;
-        let split = buffer.split(" ");
-        for s in split {
-            let s = s.trim();
-            if s.len() == 0 {
-                continue;
-            }
-            if let Ok(f) = f64::from_str(s) {
-                stack.push(Cell::Num(f));
-            } else if s.starts_with("0x") {
-                stack.push(Cell::Word(i64::from_be_bytes(
-                    u64::from_str_radix(s.strip_prefix("0x").unwrap(), 16)
-                        .unwrap()
-                        .to_be_bytes(),
-                )));
-            } else if date_pattern.is_match(s) {
-                let mut date_parts = s.split("-");
-                stack.push(Cell::Date(Date::new(
-                    i16::from_str(date_parts.next().unwrap()).unwrap(),
-                    (u16::from_str(date_parts.next().unwrap()).unwrap() - 1).into(),
-                    u8::from_str(date_parts.next().unwrap()).unwrap(),
-                )));
-            } else if s.starts_with("'") {
-                stack.push(Cell::Str(s[1..].to_string()));
-            } else if let Some((op, stack)) = find_op(s, &ops, &mut stack) {
-                let mut params = vec![];
-                let plen = op.1.len();
-                for _ in 1..=plen {
-                    params.push(stack.pop().unwrap());
-                }
-                stack.append(&mut op.2(params));
-            } else if s == "conv" {
-                let a = stack.pop();
-                let b = stack.pop();
-                if let Some(Cell::Str(a)) = a {
-                    if let Some(Cell::Str(b)) = b {
-                        if let Some(ua) = units.get(&a) {
-                            if let Some(ub) = units.get(&b) {
-                                let u = ub.clone() / ua.clone();
-
-                                if let UnitExpr::Coef(c, u) = u {
-                                    stack.push(Cell::Num(c.as_f64()));
-                                    let u = format!("{}", u);
-                                    if u.len() > 0 {
-                                        stack.push(Cell::Str(u));
-                                    }
-                                } else {
-                                    stack.push(Cell::Str(format!("{}", u)));
-                                }
-                            } else {
-                                println!("no unit found for {b}");
-                            }
-                        } else {
-                            println!("no unit found for {a}");
-                        }
-                    } else {
-                        println!("2nd not a String: {b:?}");
+        if stdin.read_line(&mut buffer)? == 0 {
+            return Ok(());
+        }
+        match lex(&buffer) {
+            Ok(tokens) => {
+                let more = calc.eval(&tokens);
+                if settings::get() != before {
+                    if let Err(e) = settings::save() {
+                        println!("{e}");
                     }
-                } else {
-                    println!("1st not a String: {a:?}");
-                }
-            } else if s == "pl" {
-                let v = stack.last();
-                if let Some(v) = v {
-                    match v {
-                        Cell::Num(v) => println!("N {v:?}"),
-                        Cell::Date(v) => println!("D {v:?}"),
-                        Cell::Days(v) => println!("Ds {v:?}"),
-                        Cell::Word(v) => {
-                            print!("W b");
-                            for i in (0..=56).rev().step_by(8) {
-                                let p1 = (v >> (i + 4)) & 0xf;
-                                let p2 = (v >> i) & 0xf;
-                                print!("{p1:04b} {p2:04b}  ");
-                            }
-                            println!();
-                            print!("W x");
-                            for i in (0..=56).rev().step_by(8) {
-                                let p = (v >> i) & 0xff;
-                                print!("{p:02X} ");
-                            }
-                            println!();
-                            println!("W {v}i64");
-                            let u = v.cast_unsigned();
-                            println!("W {u}u64");
-                            // let f = f64::from_bits(v.cast_unsigned());
-                            // println!("W {f}f64")
-                        }
-                        Cell::Str(v) => println!("S {v:?}"),
-                    };
                 }
-            } else if s == "p" || s == "pd" {
-                let v = if s == "pd" {
-                    stack.pop()
-                } else {
-                    // Barf
-                    stack.last().map(|x| x.clone())
-                };
-                if let Some(v) = v {
-                    match v {
-                        Cell::Num(v) => println!("N {v:?}"),
-                        Cell::Date(v) => println!("D {v:?}"),
-                        Cell::Days(v) => println!("Ds {v:?}"),
-                        Cell::Word(v) => {
-                            print!("W b");
-                            for i in (0..=56).rev().step_by(8) {
-                                let p1 = (v >> (i + 4)) & 0xf;
-                                let p2 = (v >> i) & 0xf;
-                                print!("{p1:04b} {p2:04b}  ");
-                            }
-                            println!();
-                        }
-                        Cell::Str(v) => println!("S {v:?}"),
-                    };
+                if !more {
+                    return Ok(());
                 }
-            } else if s == "clr" {
-                while stack.pop().is_some() {}
-            } else if s == "ps" {
-                let l = stack.len();
-                println!("Stack is {l} entries deep");
-                for (i, e) in stack.iter().rev().enumerate() {
-                    println!(" {i}: {e:?}");
-                }
-                println!();
-            } else if s == "q" {
-                return Ok(());
-            } else {
-                stack.push(Cell::Str(s.to_owned()));
             }
+            Err(e) => println!("{e}"),
         }
     }
 }
+
+#[cfg(test)]
+mod main_test {
+    use super::*;
+    use crate::units::builtin_units;
+    /// The stack after evaluating `text`, top last.
+    fn run(text: &str) -> Vec<Cell> {
+        let mut calc = Calc::new(builtin_units());
+        calc.eval(&lex(text).unwrap());
+        calc.stack
+    }
+    fn shown(text: &str) -> Vec<String> {
+        run(text).iter().map(|c| c.to_string()).collect()
+    }
+    #[test]
+    pub fn strings() {
+        assert_eq!(shown("'abc '^b rematch"), ["false"]);
+        assert_eq!(shown("'abc 'b rematch"), ["true"]);
+        assert_eq!(shown("255n hex num>str"), ["0xff"]);
+        assert_eq!(shown("3|4 num>str 1.50d num>str"), ["3|4", "1.50d"]);
+    }
+    #[test]
+    pub fn branches() {
+        assert_eq!(shown("false if [ 1 then ] else 2 then"), ["2"]);
+        assert_eq!(shown("true if [ 1 else ] else 2 then call"), ["1"]);
+        assert_eq!(shown("false if 1 else true if 2 else 3 then then"), ["2"]);
+    }
+    #[test]
+    pub fn literals() {
+        assert_eq!(shown("{ M{ 1 2 ; 3 4 } 5 }"), ["{ M{ 1 2 ; 3 4 } 5 }"]);
+        assert_eq!(shown("M{ { 1 2 } len 3 ; 4 5 }"), ["M{ 2 3 ; 4 5 }"]);
+        assert_eq!(shown("{ 1 { 2 3 } }"), ["{ 1 { 2 3 } }"]);
+    }
+    #[test]
+    pub fn angles() {
+        let re = |text: &str| run(text)[0].as_complex().re;
+        assert!((re("deg 30+0i sin") - 0.5).abs() < 1e-12);
+        assert!((re("deg 0.5+0i asin") - 30.0).abs() < 1e-9);
+        assert!((re("turn 0.5+0i cos") + 1.0).abs() < 1e-12);
+        let half_pi = std::f64::consts::FRAC_PI_2;
+        assert!((re("rad complex 2 asin") - half_pi).abs() < 1e-12);
+        // Out of range, so retried as complex, still in degrees.
+        assert!((re("deg complex 2 asin") - 90.0).abs() < 1e-9);
+        assert_eq!(
+            shown("complex -1 sqrt"),
+            [Complex::new(0.0, 1.0).to_string()]
+        );
+    }
+    #[test]
+    pub fn integers() {
+        assert_eq!(shown("0x7fffffffffffffff 1 w +"), ["9223372036854775808"]);
+        assert_eq!(shown("0x100000000 dup *"), ["18446744073709551616"]);
+        assert_eq!(shown("3 w 4 w *"), ["0xc"]);
+        assert_eq!(shown("2n 4000000000n pow"), ["2", "4000000000"]);
+        assert_eq!(shown("100000n fact"), ["100000"]);
+    }
+    #[test]
+    pub fn intervals() {
+        let iv = |text: &str| run(text)[0].as_interval();
+        let a = iv("[1,1] [1,1] atan2");
+        assert!(a.contains(std::f64::consts::FRAC_PI_4));
+        assert!(iv("deg [1,1] [1,1] atan2").contains(45.0));
+        assert!(iv("[180,180] d2r").contains(std::f64::consts::PI));
+        assert!(iv("[1,2] r2d").contains(1f64.to_degrees()));
+    }
+    #[test]
+    pub fn rationals() {
+        assert_eq!(shown("-3|4 abs"), ["3|4"]);
+        assert_eq!(
+            shown("-9223372036854775808|1 abs"),
+            ["-9223372036854775808"]
+        );
+        assert_eq!(shown("1|1 2000000000 pow"), ["1"]);
+        assert_eq!(shown("2|1 2000000000 pow"), ["2", "2000000000"]);
+        assert_eq!(shown("2|3 -3 pow"), ["27|8"]);
+    }
+    #[test]
+    pub fn quantities() {
+        assert_eq!(shown("1 'hr u 30 'min u /"), ["2"]);
+        assert_eq!(shown("3 'ft u 3 'ft u /"), ["1"]);
+        assert_eq!(shown("6 'inch u 1 'ft u / 2 *"), ["1"]);
+        // The unit mode applies to arithmetic, not to moving things around.
+        assert_eq!(shown("ubase 3 'ft u dup swap"), ["3 ft", "3 ft"]);
+        assert_eq!(shown("ubase 5 'ft u 2 *"), ["3.048 m"]);
+        assert_eq!(shown("usi 2 'kg u 3 'm/s^2 u *"), ["6 N"]);
+        assert_eq!(shown("usi 2 'kg u 3 'm/s^2 u * 2 'm u"), ["6 N", "2 m"]);
+    }
+    #[test]
+    pub fn sigfigs() {
+        assert_eq!(shown("sigfig M{ 1 2 ; 3 4 } det"), ["-2"]);
+        assert_eq!(shown("sigfig 7 8 9 3 ->list 1 nth"), ["8"]);
+        assert_eq!(shown("sigfig 3 'ft u"), ["3 ft"]);
+        assert_eq!(shown("sigfig 'a 'b 2 ', join"), ["a,b"]);
+        assert_eq!(shown("sigfig 1.20 sin"), ["0.932"]);
+        assert_eq!(shown("sigfig 2.0 atan"), ["1.1"]);
+    }
+}
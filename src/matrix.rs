@@ -0,0 +1,428 @@
+use std::fmt;
+
+/// A dense, row-major matrix of floats.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Matrix {
+    pub rows: usize,
+    pub cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn zeros(rows: usize, cols: usize) -> Matrix {
+        Matrix {
+            rows,
+            cols,
+            data: vec![0.0; rows * cols],
+        }
+    }
+
+    pub fn identity(n: usize) -> Matrix {
+        let mut m = Matrix::zeros(n, n);
+        for i in 0..n {
+            m.set(i, i, 1.0);
+        }
+        m
+    }
+
+    pub fn from_rows(rows: Vec<Vec<f64>>) -> Result<Matrix, String> {
+        let cols = rows.first().map(|r| r.len()).unwrap_or(0);
+        if cols == 0 {
+            return Err("a matrix needs at least one entry".to_owned());
+        }
+        if rows.iter().any(|r| r.len() != cols) {
+            return Err("every row of a matrix must be the same length".to_owned());
+        }
+        Ok(Matrix {
+            rows: rows.len(),
+            cols,
+            data: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn to_rows(&self) -> Vec<Vec<f64>> {
+        self.data.chunks(self.cols).map(|r| r.to_vec()).collect()
+    }
+
+    pub fn get(&self, r: usize, c: usize) -> f64 {
+        self.data[r * self.cols + c]
+    }
+
+    pub fn set(&mut self, r: usize, c: usize, v: f64) {
+        self.data[r * self.cols + c] = v;
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut t = Matrix::zeros(self.cols, self.rows);
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                t.set(c, r, self.get(r, c));
+            }
+        }
+        t
+    }
+
+    pub fn scale(&self, k: f64) -> Matrix {
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.iter().map(|x| x * k).collect(),
+        }
+    }
+
+    fn zip_with(&self, rhs: &Matrix, f: fn(f64, f64) -> f64) -> Result<Matrix, String> {
+        if self.rows != rhs.rows || self.cols != rhs.cols {
+            return Err(format!(
+                "matrix sizes don't match: {}x{} and {}x{}",
+                self.rows, self.cols, rhs.rows, rhs.cols
+            ));
+        }
+        Ok(Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self
+                .data
+                .iter()
+                .zip(&rhs.data)
+                .map(|(a, b)| f(*a, *b))
+                .collect(),
+        })
+    }
+
+    pub fn add(&self, rhs: &Matrix) -> Result<Matrix, String> {
+        self.zip_with(rhs, |a, b| a + b)
+    }
+
+    pub fn sub(&self, rhs: &Matrix) -> Result<Matrix, String> {
+        self.zip_with(rhs, |a, b| a - b)
+    }
+
+    pub fn mul(&self, rhs: &Matrix) -> Result<Matrix, String> {
+        if self.cols != rhs.rows {
+            return Err(format!(
+                "can't multiply {}x{} by {}x{}",
+                self.rows, self.cols, rhs.rows, rhs.cols
+            ));
+        }
+        let mut m = Matrix::zeros(self.rows, rhs.cols);
+        for r in 0..self.rows {
+            for c in 0..rhs.cols {
+                m.set(
+                    r,
+                    c,
+                    (0..self.cols).map(|k| self.get(r, k) * rhs.get(k, c)).sum(),
+                );
+            }
+        }
+        Ok(m)
+    }
+
+    pub fn mul_vec(&self, v: &[f64]) -> Result<Vec<f64>, String> {
+        let col = Matrix {
+            rows: v.len(),
+            cols: 1,
+            data: v.to_vec(),
+        };
+        Ok(self.mul(&col)?.data)
+    }
+
+    /// LU decomposition with partial pivoting, `PA = LU`. L is unit lower
+    /// triangular.
+    pub fn lu(&self) -> Result<(Matrix, Matrix, Matrix), String> {
+        if !self.is_square() {
+            return Err("lu needs a square matrix".to_owned());
+        }
+        let n = self.rows;
+        let mut u = self.clone();
+        let mut l = Matrix::identity(n);
+        let mut p = Matrix::identity(n);
+        for k in 0..n {
+            let pivot = (k..n)
+                .max_by(|a, b| u.get(*a, k).abs().total_cmp(&u.get(*b, k).abs()))
+                .unwrap();
+            if pivot != k {
+                u.swap_rows(k, pivot);
+                p.swap_rows(k, pivot);
+                for c in 0..k {
+                    let t = l.get(k, c);
+                    l.set(k, c, l.get(pivot, c));
+                    l.set(pivot, c, t);
+                }
+            }
+            if u.get(k, k) == 0.0 {
+                continue;
+            }
+            for r in k + 1..n {
+                let f = u.get(r, k) / u.get(k, k);
+                l.set(r, k, f);
+                for c in k..n {
+                    u.set(r, c, u.get(r, c) - f * u.get(k, c));
+                }
+            }
+        }
+        Ok((l, u, p))
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        for c in 0..self.cols {
+            self.data.swap(a * self.cols + c, b * self.cols + c);
+        }
+    }
+
+    pub fn det(&self) -> Result<f64, String> {
+        let (_, u, p) = self.lu()?;
+        // Each row swap flips the sign; count them from the permutation.
+        let mut perm: Vec<usize> = (0..p.rows)
+            .map(|r| (0..p.cols).position(|c| p.get(r, c) == 1.0).unwrap())
+            .collect();
+        let mut sign = 1.0;
+        for i in 0..perm.len() {
+            while perm[i] != i {
+                let j = perm[i];
+                perm.swap(i, j);
+                sign = -sign;
+            }
+        }
+        Ok((0..u.rows).map(|i| u.get(i, i)).product::<f64>() * sign)
+    }
+
+    /// Solves `AX = B` for every column of `B`.
+    pub fn solve(&self, b: &Matrix) -> Result<Matrix, String> {
+        if b.rows != self.rows {
+            return Err(format!(
+                "can't solve a {}x{} system for {} rows",
+                self.rows, self.cols, b.rows
+            ));
+        }
+        let (l, u, p) = self.lu()?;
+        let n = self.rows;
+        let scale = self.data.iter().fold(0.0_f64, |a, x| a.max(x.abs()));
+        if (0..n).any(|i| u.get(i, i).abs() <= scale * 1e-12) {
+            return Err("matrix is singular".to_owned());
+        }
+        let pb = p.mul(b)?;
+        let mut x = Matrix::zeros(n, b.cols);
+        for c in 0..b.cols {
+            let mut y = vec![0.0; n];
+            for r in 0..n {
+                y[r] = pb.get(r, c) - (0..r).map(|k| l.get(r, k) * y[k]).sum::<f64>();
+            }
+            for r in (0..n).rev() {
+                let s: f64 = (r + 1..n).map(|k| u.get(r, k) * x.get(k, c)).sum();
+                x.set(r, c, (y[r] - s) / u.get(r, r));
+            }
+        }
+        Ok(x)
+    }
+
+    pub fn inverse(&self) -> Result<Matrix, String> {
+        self.solve(&Matrix::identity(self.rows))
+    }
+
+    /// QR decomposition by Householder reflections, `A = QR`.
+    pub fn qr(&self) -> (Matrix, Matrix) {
+        let (m, n) = (self.rows, self.cols);
+        let mut q = Matrix::identity(m);
+        let mut r = self.clone();
+        for k in 0..n.min(m.saturating_sub(1)) {
+            let norm = (k..m).map(|i| r.get(i, k).powi(2)).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                continue;
+            }
+            let alpha = if r.get(k, k) > 0.0 { -norm } else { norm };
+            let mut v: Vec<f64> = (0..m)
+                .map(|i| if i < k { 0.0 } else { r.get(i, k) })
+                .collect();
+            v[k] -= alpha;
+            let vnorm2: f64 = v.iter().map(|x| x * x).sum();
+            if vnorm2 == 0.0 {
+                continue;
+            }
+            // H = I - 2vv'/v'v, applied as R = HR and Q = QH.
+            for c in 0..n {
+                let d: f64 = (k..m).map(|i| v[i] * r.get(i, c)).sum::<f64>() * 2.0 / vnorm2;
+                for (i, vi) in v.iter().enumerate().skip(k) {
+                    r.set(i, c, r.get(i, c) - d * vi);
+                }
+            }
+            for row in 0..m {
+                let d: f64 = (k..m).map(|i| q.get(row, i) * v[i]).sum::<f64>() * 2.0 / vnorm2;
+                for (i, vi) in v.iter().enumerate().skip(k) {
+                    q.set(row, i, q.get(row, i) - d * vi);
+                }
+            }
+        }
+        (q, r)
+    }
+
+    /// Eigenvalues of a symmetric matrix by cyclic Jacobi rotations, in
+    /// ascending order.
+    pub fn eigen_symmetric(&self) -> Result<Vec<f64>, String> {
+        if !self.is_square() {
+            return Err("eigenvalues need a square matrix".to_owned());
+        }
+        let n = self.rows;
+        let scale = self
+            .data
+            .iter()
+            .fold(0.0_f64, |a, x| a.max(x.abs()))
+            .max(1.0);
+        for r in 0..n {
+            for c in 0..r {
+                if (self.get(r, c) - self.get(c, r)).abs() > 1e-9 * scale {
+                    return Err("matrix is not symmetric".to_owned());
+                }
+            }
+        }
+        let mut a = self.clone();
+        for _ in 0..100 {
+            let off: f64 = (0..n)
+                .flat_map(|r| (0..n).filter(move |c| *c != r).map(move |c| (r, c)))
+                .map(|(r, c)| a.get(r, c).powi(2))
+                .sum();
+            if off < 1e-22 * scale * scale {
+                break;
+            }
+            for p in 0..n {
+                for q in p + 1..n {
+                    let apq = a.get(p, q);
+                    if apq == 0.0 {
+                        continue;
+                    }
+                    let theta = (a.get(q, q) - a.get(p, p)) / (2.0 * apq);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let t = if theta == 0.0 { 1.0 } else { t };
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for k in 0..n {
+                        let akp = a.get(k, p);
+                        let akq = a.get(k, q);
+                        a.set(k, p, c * akp - s * akq);
+                        a.set(k, q, s * akp + c * akq);
+                    }
+                    for k in 0..n {
+                        let apk = a.get(p, k);
+                        let aqk = a.get(q, k);
+                        a.set(p, k, c * apk - s * aqk);
+                        a.set(q, k, s * apk + c * aqk);
+                    }
+                }
+            }
+        }
+        let mut eig: Vec<f64> = (0..n).map(|i| a.get(i, i)).collect();
+        eig.sort_by(|a, b| a.total_cmp(b));
+        Ok(eig)
+    }
+}
+
+impl fmt::Display for Matrix {
+    /// One row per line with the columns lined up on the decimal point.
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cells: Vec<Vec<String>> = self
+            .to_rows()
+            .iter()
+            .map(|r| r.iter().map(|x| format!("{x}")).collect())
+            .collect();
+        let split = |s: &String| match s.find('.') {
+            Some(i) => (i, s.len() - i),
+            None => (s.len(), 0),
+        };
+        let mut widths = vec![(0, 0); self.cols];
+        for row in &cells {
+            for (c, s) in row.iter().enumerate() {
+                let (int, frac) = split(s);
+                widths[c] = (widths[c].0.max(int), widths[c].1.max(frac));
+            }
+        }
+        for (r, row) in cells.iter().enumerate() {
+            if r > 0 {
+                formatter.write_str("\n")?;
+            }
+            formatter.write_str("[")?;
+            for (c, s) in row.iter().enumerate() {
+                let (int, frac) = split(s);
+                let (iw, fw) = widths[c];
+                write!(
+                    formatter,
+                    " {}{s}{}",
+                    " ".repeat(iw - int),
+                    " ".repeat(fw - frac)
+                )?;
+            }
+            formatter.write_str(" ]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod matrix_test {
+    use super::*;
+    fn m(rows: Vec<Vec<f64>>) -> Matrix {
+        Matrix::from_rows(rows).unwrap()
+    }
+    fn close(a: &Matrix, b: &Matrix) -> bool {
+        a.rows == b.rows
+            && a.cols == b.cols
+            && a.data
+                .iter()
+                .zip(&b.data)
+                .all(|(x, y)| (x - y).abs() < 1e-9)
+    }
+    #[test]
+    pub fn arithmetic() {
+        let a = m(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        let b = m(vec![vec![0.0, 1.0], vec![1.0, 0.0]]);
+        assert_eq!(a.mul(&b).unwrap(), m(vec![vec![2.0, 1.0], vec![4.0, 3.0]]));
+        assert_eq!(a.add(&b).unwrap(), m(vec![vec![1.0, 3.0], vec![4.0, 4.0]]));
+        assert_eq!(a.transpose(), m(vec![vec![1.0, 3.0], vec![2.0, 4.0]]));
+        assert!(a.mul(&Matrix::zeros(3, 1)).is_err());
+        assert!(Matrix::from_rows(vec![vec![1.0], vec![1.0, 2.0]]).is_err());
+    }
+    #[test]
+    pub fn det_inverse_solve() {
+        let a = m(vec![
+            vec![0.0, 2.0, 1.0],
+            vec![1.0, 1.0, 0.0],
+            vec![2.0, 0.0, 3.0],
+        ]);
+        assert!((a.det().unwrap() - -8.0).abs() < 1e-12);
+        let inv = a.inverse().unwrap();
+        assert!(close(&a.mul(&inv).unwrap(), &Matrix::identity(3)));
+        let x = a.solve(&m(vec![vec![7.0], vec![3.0], vec![11.0]])).unwrap();
+        assert!(close(&x, &m(vec![vec![1.0], vec![2.0], vec![3.0]])));
+        assert!(m(vec![vec![1.0, 2.0], vec![2.0, 4.0]]).inverse().is_err());
+    }
+    #[test]
+    pub fn decompositions() {
+        let a = m(vec![
+            vec![12.0, -51.0, 4.0],
+            vec![6.0, 167.0, -68.0],
+            vec![-4.0, 24.0, -41.0],
+        ]);
+        let (l, u, p) = a.lu().unwrap();
+        assert!(close(&p.mul(&a).unwrap(), &l.mul(&u).unwrap()));
+        let (q, r) = a.qr();
+        assert!(close(&q.mul(&r).unwrap(), &a));
+        assert!(close(&q.transpose().mul(&q).unwrap(), &Matrix::identity(3)));
+        assert!(r.get(1, 0).abs() < 1e-9 && r.get(2, 0).abs() < 1e-9 && r.get(2, 1).abs() < 1e-9);
+    }
+    #[test]
+    pub fn eigenvalues() {
+        let a = m(vec![vec![2.0, 1.0], vec![1.0, 2.0]]);
+        let e = a.eigen_symmetric().unwrap();
+        assert!((e[0] - 1.0).abs() < 1e-9 && (e[1] - 3.0).abs() < 1e-9);
+        assert!(m(vec![vec![1.0, 2.0], vec![3.0, 4.0]])
+            .eigen_symmetric()
+            .is_err());
+    }
+    #[test]
+    pub fn display() {
+        let a = m(vec![vec![1.5, -20.0], vec![10.25, 3.0]]);
+        assert_eq!(format!("{a}"), "[  1.5  -20 ]\n[ 10.25   3 ]");
+    }
+}
@@ -0,0 +1,230 @@
+use regex::Regex;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+thread_local! {
+    static NEXT_SOURCE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A value with a standard uncertainty, propagated to first order.
+///
+/// Rather than just the uncertainty, this keeps how much each independent
+/// measurement it was computed from contributes to it, so `x x -` is exactly
+/// zero and `x x *` is as uncertain as `x sq` rather than treating the two
+/// as unrelated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measured {
+    pub value: f64,
+    /// `(source, ∂value/∂source × σ_source)`, sorted by source.
+    deps: Vec<(u64, f64)>,
+}
+
+impl Measured {
+    /// A new, independent measurement.
+    pub fn new(value: f64, sigma: f64) -> Measured {
+        let source = NEXT_SOURCE.with(|n| {
+            let id = n.get();
+            n.set(id + 1);
+            id
+        });
+        Measured {
+            value,
+            deps: if sigma == 0.0 {
+                vec![]
+            } else {
+                vec![(source, sigma.abs())]
+            },
+        }
+    }
+
+    /// A value known exactly.
+    pub fn exact(value: f64) -> Measured {
+        Measured {
+            value,
+            deps: vec![],
+        }
+    }
+
+    /// Parses the concise form `1.234(56)`, meaning 1.234 ± 0.056.
+    pub fn parse(s: &str) -> Option<Measured> {
+        let re = Regex::new(r"^(?<value>[-+]?\d*\.?(?<frac>\d*))\((?<sigma>\d+)\)$").unwrap();
+        let c = re.captures(s)?;
+        let value = f64::from_str(&c["value"]).ok()?;
+        let sigma = f64::from_str(&c["sigma"]).ok()? / 10f64.powi(c["frac"].len() as i32);
+        Some(Measured::new(value, sigma))
+    }
+
+    pub fn sigma(&self) -> f64 {
+        self.deps.iter().fold(0.0, |acc, (_, c)| acc.hypot(*c))
+    }
+
+    /// `f(self)`, given `f` and its derivative.
+    pub fn map(&self, f: fn(f64) -> f64, df: fn(f64) -> f64) -> Measured {
+        let d = df(self.value);
+        Measured {
+            value: f(self.value),
+            deps: self.deps.iter().map(|(s, c)| (*s, c * d)).collect(),
+        }
+    }
+
+    pub fn scale(&self, k: f64) -> Measured {
+        Measured {
+            value: self.value * k,
+            deps: self.deps.iter().map(|(s, c)| (*s, c * k)).collect(),
+        }
+    }
+
+    /// A result with the value `value` and the partial derivatives `da` and
+    /// `db` with respect to `a` and `b`.
+    fn combine(a: &Measured, b: &Measured, value: f64, da: f64, db: f64) -> Measured {
+        let mut deps = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < a.deps.len() || j < b.deps.len() {
+            let (sa, ca) = a.deps.get(i).copied().unwrap_or((u64::MAX, 0.0));
+            let (sb, cb) = b.deps.get(j).copied().unwrap_or((u64::MAX, 0.0));
+            match sa.cmp(&sb) {
+                Ordering::Less => {
+                    deps.push((sa, ca * da));
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    deps.push((sb, cb * db));
+                    j += 1;
+                }
+                Ordering::Equal => {
+                    deps.push((sa, ca * da + cb * db));
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        Measured { value, deps }
+    }
+
+    pub fn add(&self, rhs: &Measured) -> Measured {
+        Measured::combine(self, rhs, self.value + rhs.value, 1.0, 1.0)
+    }
+
+    pub fn sub(&self, rhs: &Measured) -> Measured {
+        Measured::combine(self, rhs, self.value - rhs.value, 1.0, -1.0)
+    }
+
+    pub fn mul(&self, rhs: &Measured) -> Measured {
+        Measured::combine(self, rhs, self.value * rhs.value, rhs.value, self.value)
+    }
+
+    pub fn div(&self, rhs: &Measured) -> Measured {
+        let (x, y) = (self.value, rhs.value);
+        Measured::combine(self, rhs, x / y, 1.0 / y, -x / (y * y))
+    }
+
+    pub fn pow(&self, rhs: &Measured) -> Measured {
+        let (x, y) = (self.value, rhs.value);
+        let value = x.powf(y);
+        // d/dy is x^y ln x, which is zero rather than NaN when y is exact.
+        let dy = if rhs.deps.is_empty() {
+            0.0
+        } else {
+            value * x.ln()
+        };
+        Measured::combine(self, rhs, value, y * x.powf(y - 1.0), dy)
+    }
+
+    pub fn atan2(&self, rhs: &Measured) -> Measured {
+        let (y, x) = (self.value, rhs.value);
+        let r2 = x * x + y * y;
+        Measured::combine(self, rhs, y.atan2(x), x / r2, -y / r2)
+    }
+
+    pub fn hypot(&self, rhs: &Measured) -> Measured {
+        let (x, y) = (self.value, rhs.value);
+        let h = x.hypot(y);
+        Measured::combine(self, rhs, h, x / h, y / h)
+    }
+
+    /// The value and uncertainty rounded to two significant figures of the
+    /// uncertainty, and the number of decimal places they were rounded to
+    /// (negative for tens, hundreds, ...).
+    fn rounded(&self) -> (f64, f64, i32) {
+        let sigma = self.sigma();
+        if sigma == 0.0 || !sigma.is_finite() {
+            return (self.value, sigma, 0);
+        }
+        let mut places = 1 - sigma.log10().floor() as i32;
+        // Rounding 0.0995 up to 0.100 carries into a third digit.
+        if (sigma * 10f64.powi(places)).round() >= 100.0 {
+            places -= 1;
+        }
+        let k = 10f64.powi(places);
+        (
+            (self.value * k).round() / k,
+            (sigma * k).round() / k,
+            places,
+        )
+    }
+
+    /// `1.234 ± 0.056`.
+    pub fn plus_minus(&self) -> String {
+        let (value, sigma, places) = self.rounded();
+        if sigma == 0.0 {
+            return format!("{value} ± 0");
+        }
+        let p = places.max(0) as usize;
+        format!("{value:.p$} ± {sigma:.p$}")
+    }
+}
+
+/// In the concise form it's entered in, e.g. `1.234(56)`.
+impl fmt::Display for Measured {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (value, sigma, places) = self.rounded();
+        if sigma == 0.0 {
+            return write!(formatter, "{value}(0)");
+        }
+        let p = places.max(0) as usize;
+        let digits = (sigma * 10f64.powi(places.max(0))).round();
+        write!(formatter, "{value:.p$}({digits})")
+    }
+}
+
+#[cfg(test)]
+mod measured_test {
+    use super::*;
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+    #[test]
+    pub fn parse_and_print() {
+        let m = Measured::parse("1.234(56)").unwrap();
+        assert_eq!(m.value, 1.234);
+        assert!(close(m.sigma(), 0.056));
+        assert_eq!(m.to_string(), "1.234(56)");
+        assert_eq!(Measured::new(9.81234, 0.0123).to_string(), "9.812(12)");
+        assert_eq!(Measured::new(12345.0, 1234.0).plus_minus(), "12300 ± 1200");
+        assert_eq!(Measured::new(12345.0, 1234.0).to_string(), "12300(1200)");
+        assert_eq!(Measured::new(2.5, 0.0).plus_minus(), "2.5 ± 0");
+        assert_eq!(Measured::new(1.2345, 0.0995).to_string(), "1.23(10)");
+        assert_eq!(Measured::new(1.2345, 0.0995).plus_minus(), "1.23 ± 0.10");
+        assert_eq!(Measured::parse("1.2(3"), None);
+    }
+    #[test]
+    pub fn propagation() {
+        let x = Measured::new(3.0, 0.3);
+        let y = Measured::new(4.0, 0.4);
+        // independent: relative errors add in quadrature
+        let p = x.mul(&y);
+        assert_eq!(p.value, 12.0);
+        assert!(close(p.sigma(), 12.0 * (0.1f64.hypot(0.1))));
+        // correlated: the same measurement twice
+        assert_eq!(x.sub(&x).sigma(), 0.0);
+        assert!(close(x.mul(&x).sigma(), 2.0 * 3.0 * 0.3));
+        assert!(close(x.add(&x).sigma(), 0.6));
+        let s = x.map(f64::sqrt, |v| 0.5 / v.sqrt());
+        assert!(close(s.sigma(), 0.3 * 0.5 / 3f64.sqrt()));
+        let q = x.pow(&Measured::exact(2.0));
+        assert!(close(q.sigma(), 2.0 * 3.0 * 0.3));
+        assert!(close(x.hypot(&y).value, 5.0));
+    }
+}
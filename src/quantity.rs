@@ -0,0 +1,337 @@
+use crate::units::{Coef, Reduced, UnitExpr, Units};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A number with units, such as `3 ft`.
+///
+/// The units are kept as they're written, so `3 ft` stays in feet, along with
+/// what they reduce to, so arithmetic can check and convert between them
+/// without looking them up again.
+#[derive(Clone, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: UnitExpr,
+    /// `unit` in primitive units.
+    reduced: Reduced,
+}
+
+impl Quantity {
+    pub fn new(value: f64, unit: UnitExpr, reduced: Reduced) -> Quantity {
+        Quantity {
+            value,
+            unit,
+            reduced,
+        }
+        .normalized()
+    }
+
+    /// A plain number, as a quantity without units.
+    pub fn number(value: f64) -> Quantity {
+        Quantity::new(value, UnitExpr::one(), Reduced::number(Coef::unit()))
+    }
+
+    /// `r` in `unit`, which must have the same dimension.
+    pub fn from_reduced(r: &Reduced, unit: UnitExpr, reduced: Reduced) -> Result<Quantity, String> {
+        let primitive = Quantity {
+            value: r.coef.as_f64(),
+            unit: UnitExpr::one(),
+            reduced: Reduced {
+                coef: Coef::unit(),
+                dim: r.dim.clone(),
+            },
+        };
+        primitive.convert(unit, reduced)
+    }
+
+    /// Parses a quantity such as `3.5 ft`, or one in mixed units such as
+    /// `6 ft 2 in`, which is given in the first of them.
+    pub fn parse(text: &str, units: &Units) -> Result<Quantity, String> {
+        let mut parts: Vec<(f64, Vec<&str>)> = vec![];
+        for word in text.split_whitespace() {
+            let number = f64::from_str(word)
+                .ok()
+                .filter(|_| word.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)));
+            match (number, parts.last_mut()) {
+                (Some(n), _) => parts.push((n, vec![])),
+                (None, Some((_, unit))) => unit.push(word),
+                (None, None) => return Err(format!("{text} doesn't start with a number")),
+            }
+        }
+        let mut sum: Option<Quantity> = None;
+        for (n, unit) in parts {
+            let q = if unit.is_empty() {
+                Quantity::number(n)
+            } else {
+                let (unit, reduced) = units.quantity_unit(&unit.join(" "))?;
+                Quantity::new(n, unit, reduced)
+            };
+            sum = Some(match sum {
+                Some(sum) => sum.add(&q)?,
+                None => q,
+            });
+        }
+        sum.ok_or(format!("no quantity in {text:?}"))
+    }
+
+    /// The quantity in primitive units.
+    pub fn reduced(&self) -> Reduced {
+        Reduced {
+            coef: self.reduced.coef * self.value,
+            dim: self.reduced.dim.clone(),
+        }
+    }
+
+    /// Whether the dimensions have all cancelled out, as in `ft/inch`.
+    pub fn is_number(&self) -> bool {
+        self.reduced.dim.is_dimensionless()
+    }
+
+    /// The plain number it is once the units cancel, so `6 inch/ft` is 0.5.
+    pub fn number_value(&self) -> f64 {
+        self.reduced().coef.as_f64()
+    }
+
+    /// Folds the unit's coefficient into the value, so `2 1|12 ft` is
+    /// `0.1666 ft`.
+    fn normalized(self) -> Quantity {
+        let c = self.unit.coef();
+        if c.is_unit() {
+            return self;
+        }
+        Quantity {
+            value: self.value * c.as_f64(),
+            unit: self.unit.without_coef(),
+            reduced: Reduced {
+                coef: self.reduced.coef / c,
+                dim: self.reduced.dim,
+            },
+        }
+    }
+
+    /// The same quantity in `unit`, which must have the same dimension.
+    pub fn convert(&self, unit: UnitExpr, reduced: Reduced) -> Result<Quantity, String> {
+        if self.reduced.dim != reduced.dim {
+            return Err(format!(
+                "incompatible dimensions: {} vs {}",
+                self.reduced.dim, reduced.dim
+            ));
+        }
+        let k = (self.reduced.coef / reduced.coef).as_f64();
+        Ok(Quantity {
+            value: self.value * k,
+            unit,
+            reduced,
+        })
+    }
+
+    /// `rhs` in the units of `self`.
+    fn conformed(&self, rhs: &Quantity) -> Result<f64, String> {
+        if self.reduced.dim != rhs.reduced.dim {
+            return Err(format!(
+                "incompatible dimensions: {} vs {}",
+                self.reduced.dim, rhs.reduced.dim
+            ));
+        }
+        Ok(rhs.value * (rhs.reduced.coef / self.reduced.coef).as_f64())
+    }
+
+    /// Sums and differences are in the units of the left-hand side.
+    pub fn add(&self, rhs: &Quantity) -> Result<Quantity, String> {
+        Ok(Quantity {
+            value: self.value + self.conformed(rhs)?,
+            ..self.clone()
+        })
+    }
+
+    pub fn sub(&self, rhs: &Quantity) -> Result<Quantity, String> {
+        Ok(Quantity {
+            value: self.value - self.conformed(rhs)?,
+            ..self.clone()
+        })
+    }
+
+    pub fn mul(&self, rhs: &Quantity) -> Result<Quantity, String> {
+        Ok(Quantity::new(
+            self.value * rhs.value,
+            self.unit.clone() * rhs.unit.clone(),
+            (self.reduced.clone() * rhs.reduced.clone())?,
+        ))
+    }
+
+    pub fn div(&self, rhs: &Quantity) -> Result<Quantity, String> {
+        Ok(Quantity::new(
+            self.value / rhs.value,
+            self.unit.clone() / rhs.unit.clone(),
+            (self.reduced.clone() / rhs.reduced.clone())?,
+        ))
+    }
+
+    /// Raised to a whole power, as units can only have whole powers.
+    pub fn powi(&self, n: i32) -> Result<Quantity, String> {
+        let mut q = Quantity::number(1.0);
+        for _ in 0..n.unsigned_abs() {
+            q = q.mul(self)?;
+        }
+        if n < 0 {
+            Quantity::number(1.0).div(&q)
+        } else {
+            Ok(q)
+        }
+    }
+
+    /// The quantity as whole numbers of each of `units` but the last, which
+    /// takes what's left, as 3725 s is 1 hr 2 min 5 s.
+    pub fn split(&self, units: Vec<(UnitExpr, Reduced)>) -> Result<Vec<Quantity>, String> {
+        let mut rest = self.clone();
+        let mut parts = vec![];
+        let last = units.len().saturating_sub(1);
+        for (i, (unit, reduced)) in units.into_iter().enumerate() {
+            let mut part = rest.convert(unit, reduced)?;
+            if i < last {
+                // Rounding error shouldn't turn 2 min into 1 min 60 s.
+                let whole = part.value.round();
+                if (part.value - whole).abs() > 1e-9 * whole.abs().max(1.0) {
+                    part.value = part.value.trunc();
+                } else {
+                    part.value = whole;
+                }
+                rest = rest.sub(&part)?;
+            }
+            parts.push(part);
+        }
+        Ok(parts)
+    }
+
+    pub fn abs(&self) -> Quantity {
+        Quantity {
+            value: self.value.abs(),
+            ..self.clone()
+        }
+    }
+
+    /// Orders quantities with the same dimension, whatever their units.
+    pub fn compare(&self, rhs: &Quantity) -> Result<Option<Ordering>, String> {
+        Ok(self.value.partial_cmp(&self.conformed(rhs)?))
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_number() {
+            write!(formatter, "{}", self.number_value())
+        } else {
+            write!(formatter, "{} {}", self.value, self.unit)
+        }
+    }
+}
+
+impl fmt::Debug for Quantity {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{self}")
+    }
+}
+
+#[cfg(test)]
+mod quantity_test {
+    use super::*;
+    use crate::units::{builtin_units, Units};
+    fn q(units: &Units, value: f64, unit: &str) -> Quantity {
+        let (unit, reduced) = units.quantity_unit(unit).unwrap();
+        Quantity::new(value, unit, reduced)
+    }
+    #[test]
+    pub fn arithmetic() {
+        let units = builtin_units();
+        let ft = q(&units, 3.0, "ft");
+        let inch = q(&units, 6.0, "inch");
+        let sum = ft.add(&inch).unwrap();
+        assert_eq!(sum.value, 3.5);
+        assert_eq!(sum.to_string(), "3.5 ft");
+        assert_eq!(inch.add(&ft).unwrap().to_string(), "42 inch");
+        assert_eq!(ft.sub(&inch).unwrap().value, 2.5);
+        let s = q(&units, 2.0, "s");
+        assert_eq!(
+            ft.add(&s),
+            Err("incompatible dimensions: m vs s".to_owned())
+        );
+        let speed = ft.div(&s).unwrap();
+        assert_eq!(speed.value, 1.5);
+        assert_eq!(speed.to_string(), "1.5 ft/s");
+        assert_eq!(speed.mul(&s).unwrap().to_string(), "3 ft");
+        assert_eq!(s.powi(-2).unwrap().to_string(), "0.25 1/s^2");
+        assert_eq!(ft.powi(0).unwrap().to_string(), "1");
+        // Units that cancel leave a number.
+        assert!(ft.div(&ft).unwrap().is_number());
+        assert_eq!(ft.div(&inch).unwrap().to_string(), "6");
+        assert_eq!(
+            ft.compare(&q(&units, 36.0, "inch")),
+            Ok(Some(Ordering::Equal))
+        );
+    }
+    #[test]
+    pub fn conversion() {
+        let units = builtin_units();
+        let (m, reduced) = units.quantity_unit("m").unwrap();
+        let ft = q(&units, 10.0, "ft").convert(m, reduced).unwrap();
+        assert_eq!(ft.value, 3.048);
+        assert_eq!(ft.to_string(), "3.048 m");
+        let (s, reduced) = units.quantity_unit("s").unwrap();
+        assert!(ft.convert(s, reduced).is_err());
+        let mph = q(&units, 60.0, "mile/hour");
+        let (kph, reduced) = units.quantity_unit("km/hr").unwrap();
+        assert!((mph.convert(kph, reduced).unwrap().value - 96.56064).abs() < 1e-9);
+        assert_eq!(q(&units, 2.0, "1|12").to_string(), "0.16666666666666666");
+        assert!(units.quantity_unit("sqrt(m)").is_err());
+    }
+    #[test]
+    pub fn mixed_units() {
+        let units = builtin_units();
+        let list = |target: &str| -> Vec<(UnitExpr, Reduced)> {
+            let names = units.unit_list(target).unwrap();
+            names
+                .iter()
+                .map(|u| units.quantity_unit(u).unwrap())
+                .collect()
+        };
+        let parts = |q: Quantity, target: &str| -> Vec<String> {
+            q.split(list(target))
+                .unwrap()
+                .iter()
+                .map(|p| p.to_string())
+                .collect()
+        };
+        assert_eq!(
+            parts(q(&units, 3725.0, "s"), "hr;min;sec"),
+            ["1 hr", "2 min", "5 sec"]
+        );
+        assert_eq!(
+            parts(q(&units, 3725.0, "s"), "hms"),
+            ["1 hr", "2 min", "5 sec"]
+        );
+        assert_eq!(parts(q(&units, 2.0, "min"), "min;s"), ["2 min", "0 s"]);
+        let ftin = q(&units, 1.83, "m").split(list("ftin")).unwrap();
+        assert_eq!(ftin[0].to_string(), "6 ft");
+        assert!((ftin[1].value - 0.047244).abs() < 1e-6);
+        assert_eq!(parts(q(&units, -1.5, "ft"), "ft;in"), ["-1 ft", "-6 in"]);
+        assert!(q(&units, 1.0, "m").split(list("lb;oz")).is_err());
+        assert_eq!(units.unit_list("ft"), None);
+
+        let height = Quantity::parse("6 ft 2 in", &units).unwrap();
+        assert_eq!(height.unit, units.quantity_unit("ft").unwrap().0);
+        assert!((height.value - 74.0 / 12.0).abs() < 1e-12);
+        assert_eq!(
+            Quantity::parse("1.83 m", &units).unwrap().to_string(),
+            "1.83 m"
+        );
+        assert_eq!(
+            Quantity::parse("2 kg m/s^2 3 N", &units)
+                .unwrap()
+                .to_string(),
+            "5 kg m/s^2"
+        );
+        assert!(Quantity::parse("ft 2", &units).is_err());
+        assert!(Quantity::parse("6 ft 2 s", &units).is_err());
+        assert!(Quantity::parse("", &units).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Div, Mul};
+use std::str::FromStr;
+
+/// An exact fraction, kept in lowest terms with a positive denominator.
+///
+/// The arithmetic is done in `i128` and is checked, so the `checked_*`
+/// methods give `None` rather than wrapping when a result won't fit.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+fn gcd(mut x: i128, mut y: i128) -> i128 {
+    while y != 0 {
+        let t = y;
+        y = x % y;
+        x = t;
+    }
+    x.abs()
+}
+
+impl Rational {
+    pub fn new(numerator: i64, denominator: i64) -> Self {
+        Self::reduce(numerator as i128, denominator as i128)
+            .expect("Zero is an invalid denominator!")
+    }
+
+    pub fn from_int(n: i64) -> Self {
+        Self {
+            numerator: n,
+            denominator: 1,
+        }
+    }
+
+    /// Lowest terms, or `None` for a zero denominator or a result that
+    /// doesn't fit.
+    fn reduce(numerator: i128, denominator: i128) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let g = gcd(numerator, denominator);
+        let sign = denominator.signum();
+        Some(Self {
+            numerator: (sign * numerator / g).try_into().ok()?,
+            denominator: (sign * denominator / g).try_into().ok()?,
+        })
+    }
+
+    pub fn numerator(self) -> i64 {
+        self.numerator
+    }
+
+    pub fn denominator(self) -> i64 {
+        self.denominator
+    }
+
+    pub fn as_f64(self) -> f64 {
+        (self.numerator as f64) / (self.denominator as f64)
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.denominator == 1
+    }
+
+    /// Parses `3|4` or `3/4r`, optionally signed.
+    pub fn parse(s: &str) -> Option<Self> {
+        let re = Regex::new(r"^(?<num>[-+]?\d+)(?:\|(?<bar>\d+)|/(?<slash>\d+)r)$").unwrap();
+        let c = re.captures(s)?;
+        let numerator = i64::from_str(&c["num"]).ok()?;
+        let den = c.name("bar").or(c.name("slash")).unwrap().as_str();
+        Self::reduce(numerator as i128, i64::from_str(den).ok()? as i128)
+    }
+
+    /// The simplest fraction that converts back to exactly `x`, so `0.1`
+    /// gives `1/10` rather than the float's binary expansion.
+    pub fn from_f64(x: f64) -> Option<Self> {
+        let mut max_den = 1;
+        loop {
+            let r = Self::approximate(x, max_den)?;
+            if r.as_f64() == x {
+                return Some(r);
+            }
+            max_den = max_den.checked_mul(16)?;
+        }
+    }
+
+    /// The closest fraction to `x` with a denominator of at most `max_den`,
+    /// from the continued fraction of `x` and its best semiconvergent.
+    pub fn approximate(x: f64, max_den: i64) -> Option<Self> {
+        if !x.is_finite() || max_den < 1 || x.abs() >= i64::MAX as f64 {
+            return None;
+        }
+        // Convergents h/k, starting from 0/1 and 1/0.
+        let (mut h0, mut k0, mut h1, mut k1) = (0i128, 1i128, 1i128, 0i128);
+        let mut rest = x;
+        loop {
+            let a = rest.floor();
+            let ai = a as i128;
+            let (h2, k2) = (ai * h1 + h0, ai * k1 + k0);
+            if k2 > max_den as i128 || h2.abs() > i64::MAX as i128 {
+                // The best we can do is the last convergent or the largest
+                // semiconvergent that still fits.
+                let n = (max_den as i128 - k0) / k1;
+                let semi = Self::reduce(n * h1 + h0, n * k1 + k0);
+                let last = Self::reduce(h1, k1);
+                return match (semi, last) {
+                    (Some(s), Some(l)) if n > 0 => {
+                        if (s.as_f64() - x).abs() < (l.as_f64() - x).abs() {
+                            Some(s)
+                        } else {
+                            Some(l)
+                        }
+                    }
+                    (_, l) => l,
+                };
+            }
+            (h0, k0, h1, k1) = (h1, k1, h2, k2);
+            let frac = rest - a;
+            if frac == 0.0 || (h1 as f64 / k1 as f64) == x {
+                return Self::reduce(h1, k1);
+            }
+            rest = 1.0 / frac;
+        }
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        Self::reduce(
+            self.numerator as i128 * rhs.denominator as i128
+                + rhs.numerator as i128 * self.denominator as i128,
+            self.denominator as i128 * rhs.denominator as i128,
+        )
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.checked_add(Self {
+            numerator: rhs.numerator.checked_neg()?,
+            denominator: rhs.denominator,
+        })
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        Self::reduce(
+            self.numerator as i128 * rhs.numerator as i128,
+            self.denominator as i128 * rhs.denominator as i128,
+        )
+    }
+
+    /// `None` for division by zero as well as overflow.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        Self::reduce(
+            self.numerator as i128 * rhs.denominator as i128,
+            self.denominator as i128 * rhs.numerator as i128,
+        )
+    }
+
+    /// By repeated squaring, giving up at the first product that overflows.
+    pub fn checked_pow(self, e: i32) -> Option<Self> {
+        let mut base = if e < 0 {
+            Self::from_int(1).checked_div(self)?
+        } else {
+            self
+        };
+        let mut e = e.unsigned_abs();
+        let mut acc = Self::from_int(1);
+        while e > 0 {
+            if e & 1 == 1 {
+                acc = acc.checked_mul(base)?;
+            }
+            e >>= 1;
+            if e > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Some(acc)
+    }
+
+    pub fn checked_abs(self) -> Option<Self> {
+        Some(Self {
+            numerator: self.numerator.checked_abs()?,
+            denominator: self.denominator,
+        })
+    }
+
+    /// As a mixed number such as `1 1/3` or `-2 3/4`.
+    pub fn mixed(self) -> String {
+        let whole = self.numerator / self.denominator;
+        let rest = (self.numerator % self.denominator).abs();
+        if rest == 0 {
+            format!("{whole}")
+        } else if whole == 0 {
+            format!("{}/{}", self.numerator, self.denominator)
+        } else {
+            format!("{whole} {rest}/{}", self.denominator)
+        }
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.numerator as i128 * other.denominator as i128)
+            .cmp(&(other.numerator as i128 * self.denominator as i128))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// In the `3|4` form it's entered in.
+impl fmt::Display for Rational {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(formatter, "{}", self.numerator)
+        } else {
+            write!(formatter, "{}|{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+impl Div for Rational {
+    // The division of rational numbers is a closed operation.
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        if rhs.numerator == 0 {
+            panic!("Cannot divide by zero-valued `Rational`!");
+        }
+        self.checked_div(rhs).expect("`Rational` overflow")
+    }
+}
+
+impl Div<f64> for Rational {
+    // The division of rational numbers is a closed operation.
+    type Output = f64;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        let numerator = self.numerator as f64;
+        let denominator = self.denominator as f64;
+        numerator / denominator / rhs
+    }
+}
+
+impl Div<Rational> for f64 {
+    type Output = Self;
+    fn div(self, rhs: Rational) -> Self::Output {
+        let numerator = rhs.numerator as f64;
+        let denominator = rhs.denominator as f64;
+        self * denominator / numerator
+    }
+}
+
+impl Mul for Rational {
+    type Output = Self;
+
+    fn mul(self, rhs: Rational) -> Self::Output {
+        self.checked_mul(rhs).expect("`Rational` overflow")
+    }
+}
+
+impl Mul<f64> for Rational {
+    type Output = f64;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        let numerator = self.numerator as f64;
+        let denominator = self.denominator as f64;
+        numerator / denominator * rhs
+    }
+}
+
+impl Mul<Rational> for f64 {
+    type Output = f64;
+
+    fn mul(self, rhs: Rational) -> Self::Output {
+        let numerator = rhs.numerator as f64;
+        let denominator = rhs.denominator as f64;
+        self * numerator / denominator
+    }
+}
+
+#[cfg(test)]
+mod rational_test {
+    use super::*;
+    fn r(n: i64, d: i64) -> Rational {
+        Rational::new(n, d)
+    }
+    #[test]
+    pub fn parse() {
+        assert_eq!(Rational::parse("6|8"), Some(r(3, 4)));
+        assert_eq!(Rational::parse("-3/4r"), Some(r(-3, 4)));
+        assert_eq!(Rational::parse("3|0"), None);
+        assert_eq!(Rational::parse("3/4"), None);
+        assert_eq!(r(3, -6), r(-1, 2));
+    }
+    #[test]
+    pub fn arithmetic() {
+        assert_eq!(r(1, 3).checked_add(r(1, 6)), Some(r(1, 2)));
+        assert_eq!(r(1, 3).checked_sub(r(1, 2)), Some(r(-1, 6)));
+        assert_eq!(r(2, 3).checked_mul(r(-3, 4)), Some(r(-1, 2)));
+        assert_eq!(r(2, 3).checked_div(r(0, 1)), None);
+        assert_eq!(r(2, 3).checked_pow(-2), Some(r(9, 4)));
+        assert_eq!(r(i64::MAX, 1).checked_add(r(1, 1)), None);
+        assert!(r(-1, 2) < r(1, 3));
+    }
+    #[test]
+    pub fn display() {
+        assert_eq!(r(4, 3).mixed(), "1 1/3");
+        assert_eq!(r(-11, 4).mixed(), "-2 3/4");
+        assert_eq!(r(-1, 4).mixed(), "-1/4");
+        assert_eq!(r(6, 3).mixed(), "2");
+        assert_eq!(r(-4, 3).to_string(), "-4|3");
+    }
+    #[test]
+    pub fn approximation() {
+        assert_eq!(Rational::from_f64(0.1), Some(r(1, 10)));
+        assert_eq!(Rational::from_f64(-2.75), Some(r(-11, 4)));
+        assert_eq!(Rational::from_f64(f64::NAN), None);
+        let pi = std::f64::consts::PI;
+        assert_eq!(Rational::approximate(pi, 10), Some(r(22, 7)));
+        assert_eq!(Rational::approximate(pi, 1000), Some(r(355, 113)));
+        assert_eq!(Rational::approximate(pi, 100), Some(r(311, 99)));
+        assert_eq!(Rational::approximate(0.333, 5), Some(r(1, 3)));
+    }
+}
@@ -0,0 +1,320 @@
+use std::cell::RefCell;
+use std::f64::consts::TAU;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// The unit angles are taken and given in by trig ops and polar complex
+/// numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Angle {
+    Deg,
+    #[default]
+    Rad,
+    Grad,
+    Turn,
+}
+
+impl Angle {
+    /// How many of this unit make a full turn.
+    fn per_turn(self) -> f64 {
+        match self {
+            Angle::Deg => 360.0,
+            Angle::Rad => TAU,
+            Angle::Grad => 400.0,
+            Angle::Turn => 1.0,
+        }
+    }
+
+    pub fn to_radians(self, x: f64) -> f64 {
+        match self {
+            Angle::Rad => x,
+            Angle::Deg => x.to_radians(),
+            _ => x * TAU / self.per_turn(),
+        }
+    }
+
+    pub fn radians_in(self, x: f64) -> f64 {
+        match self {
+            Angle::Rad => x,
+            Angle::Deg => x.to_degrees(),
+            _ => x * self.per_turn() / TAU,
+        }
+    }
+
+    fn parse(s: &str) -> Option<Angle> {
+        match s {
+            "deg" => Some(Angle::Deg),
+            "rad" => Some(Angle::Rad),
+            "grad" => Some(Angle::Grad),
+            "turn" => Some(Angle::Turn),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Angle {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Angle::Deg => "deg",
+            Angle::Rad => "rad",
+            Angle::Grad => "grad",
+            Angle::Turn => "turn",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+/// How decimals are rounded to fit a number of places.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Rounding {
+    /// Ties go to the even neighbour, so they don't bias sums.
+    #[default]
+    HalfEven,
+    /// Ties go away from zero.
+    HalfUp,
+    /// Towards zero.
+    Truncate,
+}
+
+impl Rounding {
+    fn parse(s: &str) -> Option<Rounding> {
+        match s {
+            "halfeven" => Some(Rounding::HalfEven),
+            "halfup" => Some(Rounding::HalfUp),
+            "truncate" => Some(Rounding::Truncate),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Rounding::HalfEven => "halfeven",
+            Rounding::HalfUp => "halfup",
+            Rounding::Truncate => "truncate",
+        };
+        write!(formatter, "{name}")
+    }
+}
+
+/// What units the results of arithmetic on quantities are given in.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum UnitStyle {
+    /// Whatever units the arithmetic leaves, e.g. `ft lb/s`.
+    #[default]
+    Keep,
+    /// Primitive units, e.g. `kg m^2/s^2`.
+    Base,
+    /// SI, with named derived units such as `N` and `J` where they fit.
+    Si,
+    /// The first of these units with the right dimension, else a product of
+    /// them, e.g. `ft lb s`.
+    Prefer(Vec<String>),
+}
+
+impl UnitStyle {
+    fn parse(s: &str) -> Option<UnitStyle> {
+        match s.split_once(' ') {
+            Some(("prefer", units)) => Some(UnitStyle::Prefer(
+                units.split_whitespace().map(str::to_owned).collect(),
+            )),
+            _ => match s {
+                "keep" => Some(UnitStyle::Keep),
+                "base" => Some(UnitStyle::Base),
+                "si" => Some(UnitStyle::Si),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for UnitStyle {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnitStyle::Keep => formatter.write_str("keep"),
+            UnitStyle::Base => formatter.write_str("base"),
+            UnitStyle::Si => formatter.write_str("si"),
+            UnitStyle::Prefer(units) => write!(formatter, "prefer {}", units.join(" ")),
+        }
+    }
+}
+
+/// Modes that change how ops behave or how values are shown.
+///
+/// Ops are plain closures over their arguments, so they read these through
+/// [`get`] rather than having them passed in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Retry real ops that come out NaN with complex arguments, so
+    /// `-1 sqrt` gives `i`.
+    pub complex: bool,
+    /// Show complex numbers as `r∠θ` rather than `a+bi`.
+    pub polar: bool,
+    /// What trig ops and polar complex numbers measure angles in.
+    pub angle: Angle,
+    /// The radix big integers are shown in: 2, 8, 10 or 16.
+    pub base: u32,
+    pub rounding: Rounding,
+    /// If set, decimal results are rounded to this many places; otherwise
+    /// they keep every place they need.
+    pub places: Option<u32>,
+    /// Float literals remember their significant figures and results are
+    /// rounded to what they justify.
+    pub sigfigs: bool,
+    pub units: UnitStyle,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            complex: false,
+            polar: false,
+            angle: Angle::default(),
+            base: 10,
+            rounding: Rounding::default(),
+            places: None,
+            sigfigs: false,
+            units: UnitStyle::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// One `key value` pair per line; unknown keys and bad values are
+    /// ignored so an old or hand-edited file never stops the calculator.
+    fn parse(text: &str) -> Settings {
+        let mut settings = Settings::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(' ') else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                "complex" => settings.complex = value == "on",
+                "polar" => settings.polar = value == "on",
+                "angle" => settings.angle = Angle::parse(value).unwrap_or_default(),
+                "base" => {
+                    settings.base = match value {
+                        "2" => 2,
+                        "8" => 8,
+                        "16" => 16,
+                        _ => 10,
+                    }
+                }
+                "rounding" => settings.rounding = Rounding::parse(value).unwrap_or_default(),
+                "places" => settings.places = value.parse().ok(),
+                "sigfigs" => settings.sigfigs = value == "on",
+                "units" => settings.units = UnitStyle::parse(value).unwrap_or_default(),
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    fn serialize(&self) -> String {
+        let on_off = |b| if b { "on" } else { "off" };
+        format!(
+            "complex {}\npolar {}\nangle {}\nbase {}\nrounding {}\nplaces {}\nsigfigs {}\nunits {}\n",
+            on_off(self.complex),
+            on_off(self.polar),
+            self.angle,
+            self.base,
+            self.rounding,
+            self.places.map_or("any".to_owned(), |p| p.to_string()),
+            on_off(self.sigfigs),
+            self.units
+        )
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} {} {} base {} {}",
+            self.angle,
+            if self.complex { "complex" } else { "real" },
+            if self.polar { "polar" } else { "rect" },
+            self.base,
+            self.rounding
+        )?;
+        if let Some(p) = self.places {
+            write!(formatter, " {p} places")?;
+        }
+        if self.sigfigs {
+            formatter.write_str(" sigfigs")?;
+        }
+        if self.units != UnitStyle::Keep {
+            write!(formatter, " units {}", self.units)?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static SETTINGS: RefCell<Settings> = RefCell::new(Settings::default());
+}
+
+pub fn get() -> Settings {
+    SETTINGS.with(|s| s.borrow().clone())
+}
+
+pub fn update(f: impl FnOnce(&mut Settings)) {
+    SETTINGS.with(|s| f(&mut s.borrow_mut()))
+}
+
+/// `$JSKCALC_SETTINGS`, or `~/.jskcalc` if that isn't set.
+fn path() -> Option<PathBuf> {
+    if let Some(p) = std::env::var_os("JSKCALC_SETTINGS") {
+        return Some(PathBuf::from(p));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".jskcalc"))
+}
+
+/// Loads the saved settings, keeping the defaults if there are none.
+pub fn load() {
+    if let Some(text) = path().and_then(|p| fs::read_to_string(p).ok()) {
+        update(|s| *s = Settings::parse(&text));
+    }
+}
+
+pub fn save() -> Result<(), String> {
+    let path = path().ok_or("no settings file: HOME is not set")?;
+    fs::write(&path, get().serialize())
+        .map_err(|e| format!("couldn't save settings to {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod settings_test {
+    use super::*;
+    #[test]
+    pub fn angles() {
+        assert_eq!(Angle::Deg.to_radians(180.0), std::f64::consts::PI);
+        assert_eq!(Angle::Turn.to_radians(0.25), std::f64::consts::FRAC_PI_2);
+        assert_eq!(Angle::Grad.radians_in(std::f64::consts::PI), 200.0);
+        assert_eq!(Angle::Rad.radians_in(1.5), 1.5);
+    }
+    #[test]
+    pub fn round_trip() {
+        let settings = Settings {
+            complex: true,
+            polar: false,
+            angle: Angle::Grad,
+            base: 16,
+            rounding: Rounding::Truncate,
+            places: Some(2),
+            sigfigs: true,
+            units: UnitStyle::Prefer(vec!["ft".to_owned(), "lbf".to_owned()]),
+        };
+        assert_eq!(Settings::parse(&settings.serialize()), settings);
+        assert_eq!(
+            Settings::parse("angle furlongs\nbogus 1\npolar on\nbase 99"),
+            Settings {
+                polar: true,
+                ..Settings::default()
+            }
+        );
+    }
+}
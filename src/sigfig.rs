@@ -0,0 +1,221 @@
+use crate::complex::fmt_float;
+use regex::Regex;
+use std::fmt;
+use std::str::FromStr;
+
+/// A float known only to some number of significant figures, so results are
+/// reported no more precisely than the data they came from.
+///
+/// The value itself is kept unrounded so that rounding errors don't build up
+/// over a calculation; only display rounds.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SigFig {
+    pub value: f64,
+    /// The power of ten of the last significant digit, so `-2` for `1.20`,
+    /// or `None` for an exact value such as a constant.
+    last: Option<i32>,
+}
+
+/// The power of ten of the leading digit of `x`, taking zero as 0.
+fn magnitude(x: f64) -> i32 {
+    if x == 0.0 {
+        0
+    } else {
+        x.abs().log10().floor() as i32
+    }
+}
+
+/// The smaller of two counts of significant figures, `None` being exact.
+fn fewest(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (d, None) | (None, d) => d,
+    }
+}
+
+impl SigFig {
+    /// `value` to `digits` significant figures.
+    pub fn new(value: f64, digits: u32) -> SigFig {
+        SigFig::with_digits(value, Some(digits.max(1)))
+    }
+
+    fn with_digits(value: f64, digits: Option<u32>) -> SigFig {
+        SigFig {
+            value,
+            last: digits.map(|d| magnitude(value) - d as i32 + 1),
+        }
+    }
+
+    pub fn exact(value: f64) -> SigFig {
+        SigFig { value, last: None }
+    }
+
+    /// A float literal with the significant figures it's written with:
+    /// `1.20` has three, `0.0045` two, and `1200` two, since trailing zeros
+    /// only count after a point, as in `1200.`.
+    pub fn parse(s: &str) -> Option<SigFig> {
+        let re =
+            Regex::new(r"^[-+]?(?<int>\d*)(?:\.(?<frac>\d*))?(?:[eE](?<exp>[-+]?\d+))?$").unwrap();
+        let c = re.captures(s)?;
+        let int = &c["int"];
+        let frac = c.name("frac");
+        if int.is_empty() && frac.is_none_or(|f| f.is_empty()) {
+            return None;
+        }
+        let value = f64::from_str(s).ok().filter(|v| v.is_finite())?;
+        let exp = match c.name("exp") {
+            Some(e) => i32::from_str(e.as_str()).ok()?,
+            None => 0,
+        };
+        let last = match frac {
+            Some(f) => -(f.len() as i32),
+            None if int.trim_end_matches('0').is_empty() => 0,
+            None => (int.len() - int.trim_end_matches('0').len()) as i32,
+        };
+        Some(SigFig {
+            value,
+            last: Some(last + exp),
+        })
+    }
+
+    /// The value rounded to its last significant digit.
+    pub fn rounded(&self) -> f64 {
+        match self.last {
+            Some(last) => {
+                // Beyond the float range one way, the scale must go the other.
+                let k = 10f64.powi(-last);
+                let d = 10f64.powi(last);
+                if k.is_finite() && k != 0.0 {
+                    (self.value * k).round() / k
+                } else if d.is_finite() && d != 0.0 {
+                    (self.value / d).round() * d
+                } else {
+                    self.value
+                }
+            }
+            None => self.value,
+        }
+    }
+
+    /// How many significant figures it has, or `None` if it's exact.
+    pub fn digits(&self) -> Option<u32> {
+        let last = self.last?;
+        let r = self.rounded();
+        if r == 0.0 {
+            return Some(1);
+        }
+        Some((magnitude(r) - last + 1).max(1) as u32)
+    }
+
+    /// Sums and differences are known to the place of the less precise one.
+    pub fn add(&self, rhs: &SigFig) -> SigFig {
+        SigFig {
+            value: self.value + rhs.value,
+            last: self.last.max(rhs.last),
+        }
+    }
+
+    pub fn sub(&self, rhs: &SigFig) -> SigFig {
+        SigFig {
+            value: self.value - rhs.value,
+            last: self.last.max(rhs.last),
+        }
+    }
+
+    /// Products and quotients have as many figures as the less precise one.
+    pub fn mul(&self, rhs: &SigFig) -> SigFig {
+        SigFig::with_digits(self.value * rhs.value, fewest(self.digits(), rhs.digits()))
+    }
+
+    pub fn div(&self, rhs: &SigFig) -> SigFig {
+        SigFig::with_digits(self.value / rhs.value, fewest(self.digits(), rhs.digits()))
+    }
+
+    /// `f(self)` with the same number of figures, as for powers and roots.
+    pub fn map(&self, f: impl Fn(f64) -> f64) -> SigFig {
+        SigFig::with_digits(f(self.value), self.digits())
+    }
+
+    /// A logarithm, which has as many decimal places as its argument has
+    /// figures.
+    pub fn log(&self, f: fn(f64) -> f64) -> SigFig {
+        SigFig {
+            value: f(self.value),
+            last: self.digits().map(|d| -(d as i32)),
+        }
+    }
+
+    /// An exponential, which has as many figures as its argument has decimal
+    /// places.
+    pub fn exp(&self, f: fn(f64) -> f64) -> SigFig {
+        SigFig::with_digits(f(self.value), self.last.map(|l| (-l).max(1) as u32))
+    }
+}
+
+/// Rounded to its figures, in scientific notation when trailing zeros
+/// before the point would be ambiguous. A trailing point marks zeros as
+/// significant, as in `100.`.
+impl fmt::Display for SigFig {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Some(last), Some(digits)) = (self.last, self.digits()) else {
+            return write!(formatter, "{}", fmt_float(self.value));
+        };
+        let r = self.rounded();
+        if last > 0 || (r != 0.0 && (r.abs() < 1e-5 || r.abs() >= 1e16)) {
+            write!(formatter, "{:.*e}", digits as usize - 1, r)
+        } else {
+            write!(formatter, "{:.*}", (-last) as usize, r)?;
+            if last == 0 && r != 0.0 && r % 10.0 == 0.0 {
+                formatter.write_str(".")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for SigFig {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{self}")
+    }
+}
+
+#[cfg(test)]
+mod sigfig_test {
+    use super::*;
+    fn s(text: &str) -> SigFig {
+        SigFig::parse(text).unwrap()
+    }
+    #[test]
+    pub fn counting() {
+        assert_eq!(s("1.20").digits(), Some(3));
+        assert_eq!(s("0.0045").digits(), Some(2));
+        assert_eq!(s("1200").digits(), Some(2));
+        assert_eq!(s("1200.").digits(), Some(4));
+        assert_eq!(s("-1.20e3").digits(), Some(3));
+        assert_eq!(s("0").digits(), Some(1));
+        assert_eq!(SigFig::exact(2.0).digits(), None);
+        assert_eq!(SigFig::parse("."), None);
+        assert_eq!(SigFig::parse("inf"), None);
+    }
+    #[test]
+    pub fn display() {
+        assert_eq!(s("1.20").to_string(), "1.20");
+        assert_eq!(s("1200").to_string(), "1.2e3");
+        assert_eq!(s("100.").to_string(), "100.");
+        assert_eq!(s("0.0045").to_string(), "0.0045");
+        assert_eq!(SigFig::new(2.0 / 3.0, 3).to_string(), "0.667");
+        assert_eq!(SigFig::exact(2.5).to_string(), "2.5");
+        assert_eq!(s("1e-320").to_string(), "1e-320");
+    }
+    #[test]
+    pub fn arithmetic() {
+        let sum = s("12.11").add(&s("18.0")).add(&s("1.013"));
+        assert_eq!(sum.to_string(), "31.1");
+        assert_eq!(s("4.56").mul(&s("1.4")).to_string(), "6.4");
+        assert_eq!(s("1.20").sub(&s("1.20")).to_string(), "0.00");
+        assert_eq!(s("2.50").mul(&SigFig::exact(2.0)).to_string(), "5.00");
+        assert_eq!(s("9.0").map(f64::sqrt).to_string(), "3.0");
+        assert_eq!(s("1000.").log(f64::log10).to_string(), "3.0000");
+        assert_eq!(s("2.00").exp(f64::exp).to_string(), "7.4");
+    }
+}
@@ -0,0 +1,350 @@
+use crate::rational::Rational;
+use crate::units::Coef;
+use std::str::FromStr;
+
+/// A unit expression as written, such as `kg m/s^2` or `1|12 ft`, before
+/// any of its names are looked up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(Coef),
+    Name(String),
+    /// A function applied to an argument, e.g. `tan(arcsec)`.
+    Call(String, Box<Expr>),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Num(Coef),
+    Name(String),
+    /// One of `+ - * / ^ | ( )`, with `**` given as `^`.
+    Op(char),
+}
+
+/// The characters that end a name.
+const OPERATORS: &str = "+-*/^|();";
+
+/// A number, kept exact as a fraction when it's a decimal that fits.
+fn number(text: &str) -> Option<Coef> {
+    let (mantissa, exp) = match text.split_once(['e', 'E']) {
+        Some((m, e)) => (m, i32::from_str(e).ok()?),
+        None => (text, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let exp = exp - frac.len() as i32;
+    let digits = i64::from_str(&format!("{int}{frac}")).ok();
+    let exact = digits.and_then(|n| {
+        let scale = 10i64.checked_pow(exp.unsigned_abs())?;
+        if exp >= 0 {
+            Some(Rational::from_int(n.checked_mul(scale)?))
+        } else {
+            Some(Rational::new(n, scale))
+        }
+    });
+    match exact {
+        Some(r) => Some(Coef::Rational(r)),
+        None => f64::from_str(text).ok().map(Coef::Float),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Tok>, String> {
+    let mut toks = vec![];
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '*' && chars.get(i + 1) == Some(&'*') {
+            toks.push(Tok::Op('^'));
+            i += 2;
+        } else if OPERATORS.contains(c) {
+            toks.push(Tok::Op(c));
+            i += 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent, but not a name starting with e such as `erg`.
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '-' || chars[j] == '+') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            toks.push(Tok::Num(number(&text).ok_or(format!("bad number {text}"))?));
+        } else {
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() && !OPERATORS.contains(chars[i]) {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            // A trailing digit from 2 to 9 is a power, as in `cm3`, unless
+            // it follows an `_` or another digit.
+            let stem = name
+                .strip_suffix(|c: char| ('2'..='9').contains(&c))
+                .filter(|stem| {
+                    !stem.is_empty() && !stem.ends_with(|c: char| c == '_' || c.is_ascii_digit())
+                });
+            if let Some(stem) = stem {
+                toks.push(Tok::Name(stem.to_owned()));
+                toks.push(Tok::Op('^'));
+                toks.push(Tok::Num(number(&name[stem.len()..]).unwrap()));
+            } else {
+                toks.push(Tok::Name(name));
+            }
+        }
+    }
+    Ok(toks)
+}
+
+struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+}
+
+/// Parses a unit expression.
+///
+/// From loosest to tightest: `+` and `-`; `*`, `/` and `per`, all left to
+/// right; multiplication by juxtaposition, so `m/s s` is `m/s^2`; a unary
+/// `-`; `^` or `**`, right to left; and `|`, which divides numbers, as in
+/// `1|12`. A leading `/` is a reciprocal, as in `/s`.
+pub fn parse(s: &str) -> Result<Expr, String> {
+    let mut p = Parser {
+        toks: tokenize(s)?,
+        pos: 0,
+    };
+    if p.toks.is_empty() {
+        return Err("empty unit expression".to_owned());
+    }
+    let e = p.sum()?;
+    match p.peek() {
+        None => Ok(e),
+        Some(t) => Err(format!("unexpected {} in {s}", describe(t))),
+    }
+}
+
+fn describe(t: &Tok) -> String {
+    match t {
+        Tok::Num(c) => format!("number {}", c.as_f64()),
+        Tok::Name(n) => n.clone(),
+        Tok::Op(c) => c.to_string(),
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        if self.peek() == Some(&Tok::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut e = self.product()?;
+        loop {
+            if self.eat('+') {
+                e = Expr::Add(Box::new(e), Box::new(self.product()?));
+            } else if self.eat('-') {
+                e = Expr::Sub(Box::new(e), Box::new(self.product()?));
+            } else {
+                return Ok(e);
+            }
+        }
+    }
+
+    fn eat_div(&mut self) -> bool {
+        if self.peek() == Some(&Tok::Name("per".to_owned())) {
+            self.pos += 1;
+            true
+        } else {
+            self.eat('/')
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut e = if self.eat_div() {
+            Expr::Div(
+                Box::new(Expr::Num(Coef::unit())),
+                Box::new(self.juxtaposed()?),
+            )
+        } else {
+            self.juxtaposed()?
+        };
+        loop {
+            if self.eat('*') {
+                e = Expr::Mul(Box::new(e), Box::new(self.juxtaposed()?));
+            } else if self.eat_div() {
+                e = Expr::Div(Box::new(e), Box::new(self.juxtaposed()?));
+            } else {
+                return Ok(e);
+            }
+        }
+    }
+
+    /// Whether the next token can start a factor.
+    fn at_factor(&self) -> bool {
+        match self.peek() {
+            Some(Tok::Num(_)) | Some(Tok::Op('(')) => true,
+            Some(Tok::Name(n)) => n != "per",
+            _ => false,
+        }
+    }
+
+    fn juxtaposed(&mut self) -> Result<Expr, String> {
+        let mut e = self.unary()?;
+        while self.at_factor() {
+            e = Expr::Mul(Box::new(e), Box::new(self.unary()?));
+        }
+        Ok(e)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.primary()?;
+        if self.eat('^') {
+            Ok(Expr::Pow(Box::new(base), Box::new(self.unary()?)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let t = self.peek().cloned();
+        self.pos += 1;
+        match t {
+            Some(Tok::Num(n)) => {
+                if self.eat('|') {
+                    match self.peek().cloned() {
+                        Some(Tok::Num(d)) => {
+                            self.pos += 1;
+                            Ok(Expr::Num(n / d))
+                        }
+                        _ => Err("| must be between numbers".to_owned()),
+                    }
+                } else {
+                    Ok(Expr::Num(n))
+                }
+            }
+            Some(Tok::Name(name)) => {
+                if self.eat('(') {
+                    let arg = self.sum()?;
+                    if !self.eat(')') {
+                        return Err(format!("missing ) after {name}("));
+                    }
+                    Ok(Expr::Call(name, Box::new(arg)))
+                } else {
+                    Ok(Expr::Name(name))
+                }
+            }
+            Some(Tok::Op('(')) => {
+                let e = self.sum()?;
+                if self.eat(')') {
+                    Ok(e)
+                } else {
+                    Err("missing )".to_owned())
+                }
+            }
+            Some(t) => Err(format!("unexpected {}", describe(&t))),
+            None => Err("unexpected end of unit expression".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod unitparse_test {
+    use super::*;
+    fn name(n: &str) -> Box<Expr> {
+        Box::new(Expr::Name(n.to_owned()))
+    }
+    fn num(n: i64, d: i64) -> Box<Expr> {
+        Box::new(Expr::Num(Coef::Rational(Rational::new(n, d))))
+    }
+    #[test]
+    pub fn precedence() {
+        // Juxtaposition binds tighter than `/`.
+        assert_eq!(
+            parse("m/s s"),
+            Ok(Expr::Div(
+                name("m"),
+                Box::new(Expr::Mul(name("s"), name("s")))
+            ))
+        );
+        assert_eq!(
+            parse("kg*m/s^2"),
+            Ok(Expr::Div(
+                Box::new(Expr::Mul(name("kg"), name("m"))),
+                Box::new(Expr::Pow(name("s"), num(2, 1)))
+            ))
+        );
+        assert_eq!(parse("m per s"), parse("m/s"));
+        assert_eq!(
+            parse("s**-2"),
+            Ok(Expr::Pow(name("s"), Box::new(Expr::Neg(num(2, 1)))))
+        );
+        assert_eq!(parse("cm3"), Ok(Expr::Pow(name("cm"), num(3, 1))));
+        assert_eq!(parse("shoe_men0"), Ok(Expr::Name("shoe_men0".to_owned())));
+        assert_eq!(parse("foo_2"), Ok(Expr::Name("foo_2".to_owned())));
+        assert_eq!(parse("°"), Ok(Expr::Name("°".to_owned())));
+        assert_eq!(parse("/s"), Ok(Expr::Div(num(1, 1), name("s"))));
+        assert_eq!(parse("1|12 ft"), Ok(Expr::Mul(num(1, 12), name("ft"))));
+        assert_eq!(
+            parse("x K + stdtemp"),
+            Ok(Expr::Add(
+                Box::new(Expr::Mul(name("x"), name("K"))),
+                name("stdtemp")
+            ))
+        );
+        assert_eq!(
+            parse("tan(arcsec)"),
+            Ok(Expr::Call("tan".to_owned(), name("arcsec")))
+        );
+    }
+    #[test]
+    pub fn numbers() {
+        assert_eq!(
+            parse("0.3048"),
+            Ok(Expr::Num(Coef::Rational(Rational::new(381, 1250))))
+        );
+        assert_eq!(parse("1e3"), Ok(*num(1000, 1)));
+        assert_eq!(parse("1.5e-3"), Ok(*num(3, 2000)));
+        assert_eq!(parse("1e-30"), Ok(Expr::Num(Coef::Float(1e-30))));
+        assert_eq!(parse("2 erg"), Ok(Expr::Mul(num(2, 1), name("erg"))));
+    }
+    #[test]
+    pub fn errors() {
+        assert!(parse("").is_err());
+        assert!(parse("(m").is_err());
+        assert!(parse("m)").is_err());
+        assert!(parse("m ^").is_err());
+        assert!(parse("1|m").is_err());
+    }
+}
@@ -1,114 +1,15 @@
+use crate::complex::fmt_float;
+use crate::rational::Rational;
+use crate::settings::UnitStyle;
+use crate::unitparse::{self, Expr};
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
-use std::ops::{Div, Mul};
+use std::fs;
+use std::ops::{Add, Div, Mul, Neg};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub struct Rational {
-    numerator: usize,
-    denominator: usize,
-}
-
-impl Rational {
-    fn new(numerator: usize, denominator: usize) -> Self {
-        if denominator == 0 {
-            panic!("Zero is an invalid denominator!");
-        }
-        let r = Self {
-            numerator: numerator,
-            denominator: denominator,
-        };
-
-        r.simplify()
-    }
-    pub fn as_f64(self) -> f64 {
-        (self.numerator as f64) / (self.denominator as f64)
-    }
-    pub fn simplify(self) -> Self {
-        let gcd = &self.gcd();
-        Self {
-            numerator: self.numerator / gcd,
-            denominator: self.denominator / gcd,
-        }
-    }
-    pub fn gcd(&self) -> usize {
-        let mut x = self.numerator;
-        let mut y = self.denominator;
-        while y != 0 {
-            let t = y;
-            y = x % y;
-            x = t;
-        }
-        x
-    }
-}
-
-impl Div for Rational {
-    // The division of rational numbers is a closed operation.
-    type Output = Self;
-
-    fn div(self, rhs: Self) -> Self::Output {
-        if rhs.numerator == 0 {
-            panic!("Cannot divide by zero-valued `Rational`!");
-        }
-
-        let numerator = self.numerator * rhs.denominator;
-        let denominator = self.denominator * rhs.numerator;
-        Self::new(numerator, denominator)
-    }
-}
-
-impl Div<f64> for Rational {
-    // The division of rational numbers is a closed operation.
-    type Output = f64;
-
-    fn div(self, rhs: f64) -> Self::Output {
-        let numerator = self.numerator as f64;
-        let denominator = self.denominator as f64;
-        numerator / denominator / rhs
-    }
-}
-
-impl Div<Rational> for f64 {
-    type Output = Self;
-    fn div(self, rhs: Rational) -> Self::Output {
-        let numerator = rhs.numerator as f64;
-        let denominator = rhs.denominator as f64;
-        self * denominator / numerator
-    }
-}
-
-impl Mul for Rational {
-    type Output = Self;
-
-    fn mul(self, rhs: Rational) -> Self::Output {
-        let numerator = self.numerator * rhs.numerator;
-        let denominator = self.denominator * rhs.denominator;
-        Self::new(numerator, denominator)
-    }
-}
-
-impl Mul<f64> for Rational {
-    type Output = f64;
-
-    fn mul(self, rhs: f64) -> Self::Output {
-        let numerator = self.numerator as f64;
-        let denominator = self.denominator as f64;
-        numerator / denominator * rhs
-    }
-}
-
-impl Mul<Rational> for f64 {
-    type Output = f64;
-
-    fn mul(self, rhs: Rational) -> Self::Output {
-        let numerator = rhs.numerator as f64;
-        let denominator = rhs.denominator as f64;
-        self * numerator / denominator
-    }
-}
-
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Coef {
     Float(f64),
@@ -118,7 +19,7 @@ pub enum Coef {
 impl Coef {
     pub fn is_unit(&self) -> bool {
         match self {
-            Coef::Rational(l) => l.numerator == l.denominator,
+            Coef::Rational(l) => l.numerator() == l.denominator(),
             _ => false,
         }
     }
@@ -141,7 +42,10 @@ impl Div for Coef {
             (Coef::Float(l), Coef::Float(r)) => Coef::Float(l / r),
             (Coef::Rational(l), Coef::Float(r)) => Coef::Float(l / r),
             (Coef::Float(l), Coef::Rational(r)) => Coef::Float(l / r),
-            (Coef::Rational(l), Coef::Rational(r)) => Coef::Rational(l / r),
+            (Coef::Rational(l), Coef::Rational(r)) => match l.checked_div(r) {
+                Some(q) => Coef::Rational(q),
+                None => Coef::Float(l.as_f64() / r.as_f64()),
+            },
         }
     }
 }
@@ -162,6 +66,44 @@ impl Mul<f64> for Coef {
     }
 }
 
+impl Add for Coef {
+    type Output = Self;
+
+    fn add(self, rhs: Coef) -> Self::Output {
+        match (self, rhs) {
+            (Coef::Rational(l), Coef::Rational(r)) => match l.checked_add(r) {
+                Some(s) => Coef::Rational(s),
+                None => Coef::Float(l.as_f64() + r.as_f64()),
+            },
+            (l, r) => Coef::Float(l.as_f64() + r.as_f64()),
+        }
+    }
+}
+
+impl Neg for Coef {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        self * Coef::Rational(Rational::from_int(-1))
+    }
+}
+
+impl Coef {
+    /// Exact for a fraction to a whole power, otherwise a float.
+    pub fn pow(self, p: Coef) -> Coef {
+        if let (Coef::Rational(b), Coef::Rational(e)) = (self, p) {
+            if let Some(r) = i32::try_from(e.numerator())
+                .ok()
+                .filter(|_| e.is_integer())
+                .and_then(|n| b.checked_pow(n))
+            {
+                return Coef::Rational(r);
+            }
+        }
+        Coef::Float(self.as_f64().powf(p.as_f64()))
+    }
+}
+
 impl Mul for Coef {
     type Output = Self;
 
@@ -170,290 +112,1606 @@ impl Mul for Coef {
             (Coef::Float(l), Coef::Float(r)) => Coef::Float(l * r),
             (Coef::Rational(l), Coef::Float(r)) => Coef::Float(l * r),
             (Coef::Float(l), Coef::Rational(r)) => Coef::Float(l * r),
-            (Coef::Rational(l), Coef::Rational(r)) => Coef::Rational(l * r),
+            (Coef::Rational(l), Coef::Rational(r)) => match l.checked_mul(r) {
+                Some(p) => Coef::Rational(p),
+                None => Coef::Float(l.as_f64() * r.as_f64()),
+            },
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// In the form it's written in unit expressions, e.g. `1|12` or `2.5e-7`.
+impl fmt::Display for Coef {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Coef::Rational(r) => write!(formatter, "{r}"),
+            Coef::Float(f) => formatter.write_str(&fmt_float(*f)),
+        }
+    }
+}
+
+/// Writes factors and their powers as `kg m^2/s^2`, or `1/s` when there are
+/// none above the line.
+fn write_factors(formatter: &mut fmt::Formatter<'_>, factors: &[(&str, Rational)]) -> fmt::Result {
+    let side = |above: bool| -> Vec<String> {
+        factors
+            .iter()
+            .filter(|(_, p)| (p.numerator() > 0) == above)
+            .map(|(name, p)| match p.checked_abs() {
+                Some(p) if p == Rational::from_int(1) => name.to_string(),
+                abs => format!("{name}^{}", abs.unwrap_or(*p)),
+            })
+            .collect()
+    };
+    let (n, d) = (side(true), side(false));
+    let n = if n.is_empty() {
+        "1".to_owned()
+    } else {
+        n.join(" ")
+    };
+    if d.is_empty() {
+        formatter.write_str(&n)
+    } else {
+        write!(formatter, "{n}/{}", d.join(" "))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Basic(String);
 
+/// Units as they're named, such as `1|12 ft^2/s`: a coefficient times each
+/// unit to a whole power. The units are kept sorted by name with no zero
+/// powers, so equal units compare equal and `ft/ft` cancels.
 #[derive(Debug, PartialEq, Clone)]
-pub enum UnitExpr {
-    Basic(Basic),
-    Div(Vec<Basic>, Vec<Basic>),
-    Coef(Coef, Box<UnitExpr>),
+pub struct UnitExpr {
+    coef: Coef,
+    factors: BTreeMap<Basic, i64>,
 }
 
 impl fmt::Display for UnitExpr {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            UnitExpr::Basic(b) => formatter.write_str(b.0.as_ref()),
-            UnitExpr::Coef(c, e) => formatter.write_fmt(format_args!("{:?} {}", c, e)),
-            UnitExpr::Div(n, d) => {
-                if n.is_empty() {
-                    if !d.is_empty() {
-                        formatter.write_str("1 ")?;
-                    }
-                } else {
-                    for e in n {
-                        formatter.write_str(e.0.as_ref())?;
-                        formatter.write_str(" ")?;
-                    }
-                }
-                if !d.is_empty() {
-                    formatter.write_str("/ ")?;
-                    for e in d {
-                        formatter.write_str(e.0.as_ref())?;
-                        formatter.write_str(" ")?
-                    }
-                }
-                Ok(())
-            }
+        let factors: Vec<(&str, Rational)> = self
+            .factors
+            .iter()
+            .map(|(b, p)| (b.0.as_str(), Rational::from_int(*p)))
+            .collect();
+        if factors.is_empty() {
+            write!(formatter, "{}", self.coef)
+        } else if self.coef.is_unit() {
+            write_factors(formatter, &factors)
+        } else {
+            write!(formatter, "{} ", self.coef)?;
+            write_factors(formatter, &factors)
         }
     }
 }
 
 impl UnitExpr {
-    pub fn factor_out_coef(self) -> (Coef, UnitExpr) {
-        match self {
-            UnitExpr::Basic(_) => (Coef::unit(), self),
-            UnitExpr::Div(_, _) => (Coef::unit(), self),
-            UnitExpr::Coef(x, e) => (x, *e),
+    /// No units at all.
+    pub fn one() -> UnitExpr {
+        UnitExpr::number(Coef::unit())
+    }
+
+    pub fn number(coef: Coef) -> UnitExpr {
+        UnitExpr {
+            coef,
+            factors: BTreeMap::new(),
         }
     }
 
-    pub fn make(lf: Coef, rf: Coef, n: Vec<Basic>, d: Vec<Basic>) -> UnitExpr {
-        let (n, d) = Self::cancel_units(n, d);
-        let e = UnitExpr::Div(n, d);
+    pub fn basic(name: &str) -> UnitExpr {
+        UnitExpr {
+            coef: Coef::unit(),
+            factors: BTreeMap::from([(Basic(name.to_owned()), 1)]),
+        }
+    }
 
-        let c = lf / rf;
-        if c.is_unit() {
-            e
-        } else {
-            UnitExpr::Coef(c, Box::new(e))
-        }
-    }
-
-    pub fn cancel_units(n: Vec<Basic>, d: Vec<Basic>) -> (Vec<Basic>, Vec<Basic>) {
-        let mut n = n;
-        let mut d = d;
-        // I'm sure there's a better way to do this.
-        let mut rem = None;
-        let mut been_through = false;
-        while !been_through || rem.is_some() {
-            been_through = true;
-            rem = None;
-            for (ni, ne) in n.iter().enumerate() {
-                for (di, de) in d.iter().enumerate() {
-                    if ne == de {
-                        rem = Some((ni, di));
-                        break;
-                    }
-                }
-                if rem.is_some() {
-                    break;
-                }
-            }
-            if let Some((ni, di)) = rem {
-                n.remove(ni);
-                d.remove(di);
+    pub fn coef(&self) -> Coef {
+        self.coef
+    }
+
+    /// The same units without the coefficient, so `1|12 ft` is `ft`.
+    pub fn without_coef(&self) -> UnitExpr {
+        UnitExpr {
+            coef: Coef::unit(),
+            factors: self.factors.clone(),
+        }
+    }
+
+    pub fn powi(&self, p: i64) -> UnitExpr {
+        UnitExpr {
+            coef: self.coef.pow(Coef::Rational(Rational::from_int(p))),
+            factors: self
+                .factors
+                .iter()
+                .filter(|_| p != 0)
+                .map(|(b, q)| (b.clone(), q * p))
+                .collect(),
+        }
+    }
+
+    /// Adds `k` times the powers of `rhs` to those of `self`.
+    fn combine(mut self, rhs: UnitExpr, k: i64) -> UnitExpr {
+        for (b, p) in rhs.factors {
+            let power = self.factors.entry(b).or_insert(0);
+            *power += k * p;
+            if *power == 0 {
+                self.factors.retain(|_, p| *p != 0);
             }
         }
-        (n, d)
+        self
     }
 }
 
 impl Div for UnitExpr {
     type Output = Self;
     fn div(self, rhs: Self) -> Self::Output {
-        let (lf, l) = self.factor_out_coef();
-        let (rf, r) = rhs.factor_out_coef();
-
-        let (n, d) = match (l, r) {
-            (UnitExpr::Div(mut ln, mut ld), UnitExpr::Div(mut rn, mut rd)) => {
-                ln.append(&mut rd);
-                ld.append(&mut rn);
-                (ln, ld)
-            }
-            (UnitExpr::Basic(b), UnitExpr::Div(rn, mut rd)) => {
-                rd.push(b);
-                (rd, rn)
-            }
-            (UnitExpr::Div(ln, mut ld), UnitExpr::Basic(b)) => {
-                ld.push(b);
-                (ln, ld)
+        let coef = self.coef / rhs.coef;
+        UnitExpr {
+            coef,
+            ..self.combine(rhs, -1)
+        }
+    }
+}
+
+impl Mul for UnitExpr {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        let coef = self.coef * rhs.coef;
+        UnitExpr {
+            coef,
+            ..self.combine(rhs, 1)
+        }
+    }
+}
+
+/// Powers of primitive units, such as `m^3` for volume. They're kept sorted
+/// and without zero powers, so equal dimensions compare equal.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Dimension(BTreeMap<String, Rational>);
+
+impl Dimension {
+    fn primitive(name: &str) -> Dimension {
+        Dimension(BTreeMap::from([(name.to_owned(), Rational::from_int(1))]))
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The powers of `self` and `rhs`, each multiplied by `k` first.
+    fn combine(&self, rhs: &Dimension, k: i64) -> Option<Dimension> {
+        let mut powers = self.0.clone();
+        for (name, p) in &rhs.0 {
+            let p = p.checked_mul(Rational::from_int(k))?;
+            let sum = match powers.get(name) {
+                Some(q) => q.checked_add(p)?,
+                None => p,
+            };
+            if sum.numerator() == 0 {
+                powers.remove(name);
+            } else {
+                powers.insert(name.clone(), sum);
             }
-            (UnitExpr::Basic(lb), UnitExpr::Basic(rb)) => (vec![lb], vec![rb]),
-            _ => panic!("How'd we get here?"),
+        }
+        Some(Dimension(powers))
+    }
+
+    fn pow(&self, r: Rational) -> Option<Dimension> {
+        if r.numerator() == 0 {
+            return Some(Dimension::default());
+        }
+        let powers = self
+            .0
+            .iter()
+            .map(|(name, p)| Some((name.clone(), p.checked_mul(r)?)))
+            .collect::<Option<_>>()?;
+        Some(Dimension(powers))
+    }
+}
+
+/// Like `kg m^2/s^2`, with fractional powers as in `m^1|2`.
+impl fmt::Display for Dimension {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let factors: Vec<(&str, Rational)> =
+            self.0.iter().map(|(name, p)| (name.as_str(), *p)).collect();
+        write_factors(formatter, &factors)
+    }
+}
+
+/// A unit expression reduced to primitive units: a number times a
+/// dimension.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reduced {
+    pub coef: Coef,
+    pub dim: Dimension,
+}
+
+impl Reduced {
+    pub fn number(coef: Coef) -> Reduced {
+        Reduced {
+            coef,
+            dim: Dimension::default(),
+        }
+    }
+
+    /// The value, if it has no dimension.
+    pub fn as_number(&self) -> Option<Coef> {
+        self.dim.is_dimensionless().then_some(self.coef)
+    }
+
+    /// The sum, if both have the same dimension.
+    pub fn add(self, rhs: Reduced) -> Result<Reduced, String> {
+        if self.dim != rhs.dim {
+            return Err(format!("can't add {} and {}", self.dim, rhs.dim));
+        }
+        Ok(Reduced {
+            coef: self.coef + rhs.coef,
+            dim: self.dim,
+        })
+    }
+
+    /// Raised to the power `p`, which may be a fraction, as in `m^1|2`.
+    pub fn pow(self, p: Coef) -> Result<Reduced, String> {
+        if self.dim.is_dimensionless() {
+            return Ok(Reduced::number(self.coef.pow(p)));
+        }
+        let r = match p {
+            Coef::Rational(r) => r,
+            Coef::Float(f) => Rational::from_f64(f).ok_or(format!("bad power {f}"))?,
         };
+        let dim = self
+            .dim
+            .pow(r)
+            .ok_or(format!("{} to the power {r} is too complicated", self.dim))?;
+        Ok(Reduced {
+            coef: self.coef.pow(p),
+            dim,
+        })
+    }
+}
 
-        UnitExpr::make(lf, rf, n, d)
+impl fmt::Display for Reduced {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.dim.is_dimensionless() {
+            write!(formatter, "{}", self.coef.as_f64())
+        } else {
+            write!(formatter, "{} {}", self.coef.as_f64(), self.dim)
+        }
     }
 }
 
-impl Mul for UnitExpr {
-    type Output = Self;
+impl Mul for Reduced {
+    type Output = Result<Reduced, String>;
     fn mul(self, rhs: Self) -> Self::Output {
-        let (lf, l) = self.factor_out_coef();
-        let (rf, r) = rhs.factor_out_coef();
-
-        let (n, d) = match (l, r) {
-            (UnitExpr::Div(mut ln, mut ld), UnitExpr::Div(mut rn, mut rd)) => {
-                ln.append(&mut rd);
-                ld.append(&mut rn);
-                (ln, ld)
+        let dim = self
+            .dim
+            .combine(&rhs.dim, 1)
+            .ok_or(format!("{} times {} is too complicated", self.dim, rhs.dim))?;
+        Ok(Reduced {
+            coef: self.coef * rhs.coef,
+            dim,
+        })
+    }
+}
+
+impl Div for Reduced {
+    type Output = Result<Reduced, String>;
+    fn div(self, rhs: Self) -> Self::Output {
+        let dim = self
+            .dim
+            .combine(&rhs.dim, -1)
+            .ok_or(format!("{} over {} is too complicated", self.dim, rhs.dim))?;
+        Ok(Reduced {
+            coef: self.coef / rhs.coef,
+            dim,
+        })
+    }
+}
+
+/// Names for common dimensions, as powers of the primitive units of the GNU
+/// units file.
+const DIMENSION_NAMES: [(&str, &str); 36] = [
+    ("length", "m"),
+    ("mass", "kg"),
+    ("time", "s"),
+    ("temperature", "K"),
+    ("current", "A"),
+    ("amount", "mol"),
+    ("luminous intensity", "cd"),
+    ("information", "bit"),
+    ("money", "US$"),
+    ("area", "m^2"),
+    ("volume", "m^3"),
+    ("frequency", "1/s"),
+    ("velocity", "m/s"),
+    ("acceleration", "m/s^2"),
+    ("jerk", "m/s^3"),
+    ("volume flow", "m^3/s"),
+    ("density", "kg/m^3"),
+    ("linear density", "kg/m"),
+    ("momentum", "kg m/s"),
+    ("force", "kg m/s^2"),
+    ("energy", "kg m^2/s^2"),
+    ("power", "kg m^2/s^3"),
+    ("pressure", "kg/m s^2"),
+    ("viscosity", "kg/m s"),
+    ("specific energy", "m^2/s^2"),
+    ("charge", "A s"),
+    ("voltage", "kg m^2/A s^3"),
+    ("resistance", "kg m^2/A^2 s^3"),
+    ("conductance", "A^2 s^3/kg m^2"),
+    ("capacitance", "A^2 s^4/kg m^2"),
+    ("inductance", "kg m^2/A^2 s^2"),
+    ("magnetic flux", "kg m^2/A s^2"),
+    ("magnetic field", "kg/A s^2"),
+    ("concentration", "mol/m^3"),
+    ("illuminance", "cd/m^2"),
+    ("data rate", "bit/s"),
+];
+
+/// Named SI units, used where they fit rather than a product of primitive
+/// units.
+const SI_DERIVED: [&str; 13] = [
+    "N", "J", "W", "Pa", "Hz", "C", "V", "ohm", "F", "H", "Wb", "T", "S",
+];
+
+/// The definitions used when there's no units file to read.
+const BUILTIN: &str = include_str!("definitions.units");
+
+/// Where GNU units installs its definitions.
+const SYSTEM_FILES: [&str; 2] = [
+    "/usr/share/units/definitions.units",
+    "/usr/local/share/units/definitions.units",
+];
+
+/// How deeply `!include`s may nest, so a file that includes itself stops.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// How many definitions a lookup may go through, so a loop stops.
+const MAX_LOOKUP_DEPTH: usize = 32;
+
+/// A name standing for a value, as a function's parameter does.
+type Binding<'a> = Option<(&'a str, &'a Reduced)>;
+
+/// The limits of a function's argument or result, such as `[0,)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    /// The lower limit and whether it's included.
+    pub lo: Option<(f64, bool)>,
+    pub hi: Option<(f64, bool)>,
+}
+
+impl Bounds {
+    pub fn contains(&self, x: f64) -> bool {
+        let above = match self.lo {
+            Some((lo, true)) => x >= lo,
+            Some((lo, false)) => x > lo,
+            None => true,
+        };
+        let below = match self.hi {
+            Some((hi, true)) => x <= hi,
+            Some((hi, false)) => x < hi,
+            None => true,
+        };
+        above && below
+    }
+
+    /// Parses `[lo,hi]`, with `(` or `)` for an excluded end and either
+    /// number left out for no limit.
+    fn parse(s: &str) -> Option<Bounds> {
+        let re = Regex::new(r"^(?<open>[\[(])(?<lo>[^,]*),(?<hi>[^\])]*)(?<close>[\])])$").unwrap();
+        let c = re.captures(s)?;
+        let end = |text: &str, closed: bool| -> Option<Option<(f64, bool)>> {
+            if text.trim().is_empty() {
+                Some(None)
+            } else {
+                Some(Some((f64::from_str(text.trim()).ok()?, closed)))
             }
-            (UnitExpr::Basic(b), UnitExpr::Div(mut rn, rd)) => {
-                rn.push(b);
-                (rn, rd)
+        };
+        Some(Bounds {
+            lo: end(&c["lo"], &c["open"] == "[")?,
+            hi: end(&c["hi"], &c["close"] == "]")?,
+        })
+    }
+}
+
+/// A unit defined by a function of a number, such as `tempF(x)`, for scales
+/// that aren't just a multiple of some unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub param: String,
+    /// What the argument and the result are measured in, as written.
+    pub units: Option<(String, String)>,
+    pub domain: Option<Bounds>,
+    pub range: Option<Bounds>,
+    /// The result for the argument `param`.
+    pub forward: String,
+    /// The argument for a result, written in terms of the function's name.
+    pub inverse: Option<String>,
+}
+
+/// A unit defined by a table of points to interpolate between, such as a
+/// wire gauge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// What the results are measured in.
+    pub unit: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+/// Unit definitions in the GNU units format.
+///
+/// Definitions are kept as they're written and only looked up when they're
+/// used, so they can refer to ones later in the file.
+#[derive(Debug, Default)]
+pub struct Units {
+    /// Each unit's definition, `!` for a primitive unit.
+    defs: HashMap<String, String>,
+    /// Prefixes such as `kilo`, without their `-`.
+    prefixes: HashMap<String, String>,
+    functions: HashMap<String, Function>,
+    tables: HashMap<String, Table>,
+    /// Lists of units for mixed output, such as `hms` for `hr;min;sec`.
+    unitlists: HashMap<String, String>,
+    /// Variables for `!var`, as given by `!set` when they're not in the
+    /// environment.
+    vars: HashMap<String, String>,
+}
+
+/// The locale `!locale` sections are chosen by, e.g. `en_US`.
+fn locale() -> String {
+    let lang = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|v| std::env::var(v).ok())
+        .find(|v| !v.is_empty())
+        .unwrap_or_default();
+    let lang = lang.split(['.', '@']).next().unwrap_or_default();
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        "en_US".to_owned()
+    } else {
+        lang.to_owned()
+    }
+}
+
+/// `$JSKCALC_UNITS`, or GNU units' own file if it's installed.
+fn definitions_path() -> Option<PathBuf> {
+    if let Some(p) = std::env::var_os("JSKCALC_UNITS") {
+        return Some(PathBuf::from(p));
+    }
+    SYSTEM_FILES.iter().map(PathBuf::from).find(|p| p.is_file())
+}
+
+impl Units {
+    /// Reads definitions from `text`, adding to or replacing those already
+    /// read. `dir` is where `!include`d files are looked for. Lines it can't
+    /// make sense of are skipped.
+    pub fn read(&mut self, text: &str, dir: Option<&Path>, depth: usize) {
+        // Whether each enclosing `!locale`, `!var` or `!utf8` section applies.
+        let mut sections: Vec<bool> = vec![];
+        let mut line = String::new();
+        for l in text.lines() {
+            let l = l.split('#').next().unwrap_or_default();
+            if let Some(l) = l.trim_end().strip_suffix('\\') {
+                line.push_str(l);
+                line.push(' ');
+                continue;
             }
-            (UnitExpr::Div(mut ln, ld), UnitExpr::Basic(b)) => {
-                ln.push(b);
-                (ln, ld)
+            line.push_str(l);
+            let logical = std::mem::take(&mut line);
+            let logical = logical.trim();
+            if logical.is_empty() {
+                continue;
             }
-            (UnitExpr::Basic(lb), UnitExpr::Basic(rb)) => (vec![lb, rb], vec![]),
-            _ => panic!("How'd we get here?"),
+            if let Some(directive) = logical.strip_prefix('!') {
+                let mut words = directive.split_whitespace();
+                let command = words.next().unwrap_or_default();
+                let args: Vec<&str> = words.collect();
+                match command {
+                    "locale" => sections.push(args.first() == Some(&locale().as_str())),
+                    "var" | "varnot" => {
+                        let value = args.first().and_then(|name| {
+                            std::env::var(name).ok().or(self.vars.get(*name).cloned())
+                        });
+                        let listed = value.is_some_and(|v| args[1..].contains(&v.as_str()));
+                        sections.push(listed == (command == "var"));
+                    }
+                    "utf8" => sections.push(true),
+                    "endlocale" | "endvar" | "endutf8" => {
+                        sections.pop();
+                    }
+                    _ if sections.contains(&false) => {}
+                    "set" if args.len() >= 2 => {
+                        self.vars
+                            .entry(args[0].to_owned())
+                            .or_insert(args[1].to_owned());
+                    }
+                    "include" if !args.is_empty() && depth < MAX_INCLUDE_DEPTH => {
+                        let path = match dir {
+                            Some(d) => d.join(args[0]),
+                            None => PathBuf::from(args[0]),
+                        };
+                        match fs::read_to_string(&path) {
+                            Ok(t) => self.read(&t, path.parent(), depth + 1),
+                            Err(e) => println!("couldn't include {}: {e}", path.display()),
+                        }
+                    }
+                    "unitlist" if args.len() >= 2 => {
+                        self.unitlists
+                            .insert(args[0].to_owned(), args[1..].join(" "));
+                    }
+                    // `!message`, `!prompt` and the like.
+                    _ => {}
+                }
+            } else if !sections.contains(&false) {
+                self.define(logical);
+            }
+        }
+    }
+
+    /// Adds one definition: a unit, a `prefix-`, a `function(x)` or a
+    /// `table[unit]`. Returns whether it could be made sense of.
+    fn define(&mut self, line: &str) -> bool {
+        let (name, def) = match line.split_once(char::is_whitespace) {
+            Some((name, def)) => (name, def.trim()),
+            None => return false,
         };
+        // A leading `+` marks a deliberate redefinition.
+        let name = name.strip_prefix('+').unwrap_or(name);
+        if let Some((fname, param)) = name.strip_suffix(')').and_then(|n| n.split_once('(')) {
+            let Some(f) = Function::parse(param, def) else {
+                return false;
+            };
+            self.functions.insert(fname.to_owned(), f);
+        } else if let Some((tname, unit)) = name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+            let Some(t) = Table::parse(unit, def) else {
+                return false;
+            };
+            self.tables.insert(tname.to_owned(), t);
+        } else if let Some(prefix) = name.strip_suffix('-') {
+            self.prefixes.insert(prefix.to_owned(), def.to_owned());
+        } else {
+            self.defs.insert(name.to_owned(), def.to_owned());
+        }
+        true
+    }
 
-        UnitExpr::make(lf, rf, n, d)
-    }
-}
-
-pub fn load_units() -> HashMap<String, UnitExpr> {
-    // taken from gnu units (/usr/share/units/definitions.units)
-    let lines = vec![
-        "cm !", // I have to stop this somewhere
-        "inch                    2.54 cm",
-        "in                      inch",
-        "usgallon                231 in^3 # US liquid measure is derived from",
-        "gallon                  usgallon",
-        "gal                     gallon          # the British wine gallon of 1707.",
-        "quart                   1|4 gallon      # See the \"winegallon\" entry below",
-        "pint                    1|2 quart       # more historical information.",
-        "gill                    1|4 pint",
-        "usquart                 1|4 usgallon",
-        "uspint                  1|2 usquart",
-        "usgill                  1|4 uspint",
-        "usfluidounce            1|16 uspint",
-        "usfloz                  usfluidounce",
-        "fluiddram               1|8 usfloz",
-        "minimvolume             1|60 fluiddram",
-        "qt                      quart",
-        "pt                      pint",
-        "uscup                   8 usfloz",
-        "ustablespoon            1|16 uscup",
-        "usteaspoon              1|3 ustablespoon",
-        "ustbl                   ustablespoon",
-        "ustbsp                  ustablespoon",
-        "ustblsp                 ustablespoon",
-        "ustsp                   usteaspoon",
-    ];
-    let mut units = HashMap::new();
-    let units_line =
-        Regex::new(r"(?<name>\S+)\s+((?<num>\d+(\.\d+)?)(\|(?<den>\d+))?\s+)?(?<def>[^#]*)(#.*)?")
-            .unwrap();
-    for line in lines {
-        for c in units_line.captures_iter(line) {
-            let def = c["def"].trim().to_owned();
-            let name = c["name"].trim().to_owned();
-            let mut coef = Coef::unit();
-            let mut e = if def == "!" {
-                UnitExpr::Basic(Basic(name.clone()))
+    /// Adds a definition given during a session, written as in the
+    /// definitions file, such as `pallet` and `48 widget`. A unit whose
+    /// definition doesn't reduce to primitive units is refused, leaving any
+    /// earlier definition in place.
+    pub fn define_unit(&mut self, name: &str, def: &str) -> Result<(), String> {
+        let name = name.strip_prefix('+').unwrap_or(name);
+        // Only units and prefixes are checked, so only they need restoring.
+        let (map, key) = match name.strip_suffix('-') {
+            Some(prefix) => (&self.prefixes, prefix),
+            None => (&self.defs, name),
+        };
+        let old = map.get(key).cloned();
+        if !self.define(&format!("{name} {def}")) {
+            return Err(format!("can't define {name} as {def}"));
+        }
+        if name.ends_with([')', ']']) {
+            return Ok(());
+        }
+        let check = match name.strip_suffix('-') {
+            Some(_) => self.parse(def),
+            None => self.parse(name),
+        };
+        if let Err(e) = check {
+            let map = match name.strip_suffix('-') {
+                Some(_) => &mut self.prefixes,
+                None => &mut self.defs,
+            };
+            match old {
+                Some(old) => map.insert(key.to_owned(), old),
+                None => map.remove(key),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Parses a unit expression such as `kg m/s^2`, reducing it to
+    /// primitive units.
+    pub fn parse(&self, text: &str) -> Result<Reduced, String> {
+        self.eval(&unitparse::parse(text)?, 0, None)
+    }
+
+    /// Parses the units of a quantity, such as `km/hr`, both as they're
+    /// written and reduced to primitive units.
+    pub fn quantity_unit(&self, text: &str) -> Result<(UnitExpr, Reduced), String> {
+        let e = unitparse::parse(text)?;
+        Ok((named(&e)?, self.eval(&e, 0, None)?))
+    }
+
+    /// The units `style` gives a result of dimension `dim` in, or `None` to
+    /// leave it as it is.
+    pub fn express(&self, dim: &Dimension, style: &UnitStyle) -> Option<(UnitExpr, Reduced)> {
+        let preferred: Vec<&str> = match style {
+            UnitStyle::Keep => return None,
+            UnitStyle::Base => vec![],
+            UnitStyle::Si => SI_DERIVED.to_vec(),
+            UnitStyle::Prefer(units) => units.iter().map(String::as_str).collect(),
+        };
+        if dim.is_dimensionless() {
+            return None;
+        }
+        let preferred: Vec<(UnitExpr, Reduced)> = preferred
+            .into_iter()
+            .filter_map(|u| self.quantity_unit(u).ok())
+            .collect();
+        if let Some(p) = preferred.iter().find(|(_, r)| r.dim == *dim) {
+            return Some(p.clone());
+        }
+        // Otherwise a product of powers of a unit for each primitive one.
+        let mut unit = UnitExpr::one();
+        let mut reduced = Reduced::number(Coef::unit());
+        for (name, power) in &dim.0 {
+            if !power.is_integer() {
+                return None;
+            }
+            let primitive = Dimension::primitive(name);
+            let (u, r) = preferred
+                .iter()
+                .find(|(_, r)| r.dim == primitive)
+                .cloned()
+                .unwrap_or_else(|| {
+                    let r = Reduced {
+                        coef: Coef::unit(),
+                        dim: primitive,
+                    };
+                    (UnitExpr::basic(name), r)
+                });
+            unit = unit * u.powi(power.numerator());
+            reduced = (reduced * r.pow(Coef::Rational(*power)).ok()?).ok()?;
+        }
+        Some((unit, reduced))
+    }
+
+    /// The factor to convert `from` to `to`, which must have the same
+    /// dimension, or if `to` is a function unit such as `tempC`, the number
+    /// it gives `from` for.
+    pub fn convert(&self, from: &str, to: &str) -> Result<Coef, String> {
+        if self.is_function(to.trim()) {
+            return self.invert(to.trim(), &self.parse(from)?).map(Coef::Float);
+        }
+        let (f, t) = (self.parse(from)?, self.parse(to)?);
+        if f.dim != t.dim {
+            let name = |d: &Dimension| self.dimension_name(d).map_or(d.to_string(), str::to_owned);
+            return Err(format!(
+                "incompatible dimensions: {} vs {}\n  {from} is {}\n  {to} is {}",
+                name(&f.dim),
+                name(&t.dim),
+                f.dim,
+                t.dim
+            ));
+        }
+        Ok(f.coef / t.coef)
+    }
+
+    /// What a unit measures, such as `force (kg m / s^2)`.
+    pub fn dimension(&self, text: &str) -> Result<String, String> {
+        let dim = self.parse(text)?.dim;
+        Ok(match self.dimension_name(&dim) {
+            Some(name) => format!("{name} ({dim})"),
+            None => dim.to_string(),
+        })
+    }
+
+    /// The common name for a dimension, such as `volume` for `m^3`.
+    fn dimension_name(&self, dim: &Dimension) -> Option<&'static str> {
+        if dim.is_dimensionless() {
+            return Some("dimensionless");
+        }
+        DIMENSION_NAMES
+            .iter()
+            .find(|(_, sig)| self.parse(sig).is_ok_and(|r| r.dim == *dim))
+            .map(|(name, _)| *name)
+    }
+
+    /// The units of a mixed-unit target, given as a list such as `ft;in` or
+    /// by the name of a `!unitlist` such as `hms`.
+    pub fn unit_list<'a>(&'a self, target: &'a str) -> Option<Vec<&'a str>> {
+        let list = match self.unitlists.get(target) {
+            Some(list) => list.as_str(),
+            None if target.contains(';') => target,
+            None => return None,
+        };
+        Some(
+            list.split(';')
+                .map(str::trim)
+                .filter(|u| !u.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Every unit, function and table unit, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .defs
+            .keys()
+            .chain(self.functions.keys())
+            .chain(self.tables.keys())
+            .map(String::as_str)
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The dimension given by a name such as `volume`, or else by what a unit
+    /// expression such as `m^3` reduces to.
+    pub fn dimension_of(&self, text: &str) -> Result<Dimension, String> {
+        match DIMENSION_NAMES
+            .iter()
+            .find(|(name, _)| *name == text.trim())
+        {
+            Some((_, sig)) => self.parse(sig).map(|r| r.dim),
+            None => self.parse(text).map(|r| r.dim),
+        }
+    }
+
+    /// Every unit of dimension `dim`, along with function and table units
+    /// that give results of it, such as `tempC` for temperatures.
+    pub fn conformable(&self, dim: &Dimension) -> Vec<&str> {
+        self.names()
+            .into_iter()
+            .filter(|name| {
+                let unit = match self.function_output(name) {
+                    Some(output) => self.parse(output),
+                    None if self.is_function(name) => return false,
+                    None => self.parse(name),
+                };
+                unit.is_ok_and(|u| u.dim == *dim)
+            })
+            .collect()
+    }
+
+    /// Units whose names contain `pattern`, ignoring case, or failing that,
+    /// have its letters in order, as `gl` does `gallon`.
+    pub fn search(&self, pattern: &str) -> Vec<&str> {
+        let pattern = pattern.to_lowercase();
+        let names = self.names();
+        let found: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|n| n.to_lowercase().contains(&pattern))
+            .collect();
+        if !found.is_empty() {
+            return found;
+        }
+        names
+            .into_iter()
+            .filter(|n| {
+                let mut chars = n.chars().flat_map(char::to_lowercase);
+                pattern.chars().all(|p| chars.any(|c| c == p))
+            })
+            .collect()
+    }
+
+    /// How a unit is defined, one step per line, down to primitive units,
+    /// e.g. `ustbsp = 3 ustsp`, ..., `= 1.478676478125e-05 m^3`.
+    pub fn definition(&self, name: &str) -> Result<Vec<String>, String> {
+        let name = name.trim();
+        if let Some(f) = self.functions.get(name) {
+            let mut lines = vec![format!("{name}({}) = {}", f.param, f.forward)];
+            if let Some(inverse) = &f.inverse {
+                lines.push(format!("{name} inverse = {inverse}"));
+            }
+            return Ok(lines);
+        }
+        if let Some(t) = self.tables.get(name) {
+            let points: Vec<String> = t.points.iter().map(|(x, y)| format!("{x} {y}")).collect();
+            return Ok(vec![format!("{name}[{}] = {}", t.unit, points.join(", "))]);
+        }
+        let mut lines = vec![];
+        let mut current = name.to_owned();
+        for _ in 0..MAX_LOOKUP_DEPTH {
+            let unit = match self.resolve(&current, true) {
+                Some((None, Some(unit))) => unit,
+                Some((prefix, unit)) => {
+                    let prefix = prefix.map(|p| format!("{p}-"));
+                    let spelled: Vec<&str> = prefix.as_deref().into_iter().chain(unit).collect();
+                    lines.push(format!("{current} = {}", spelled.join(" ")));
+                    match unit {
+                        Some(u) => {
+                            current = u.to_owned();
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                None => break,
+            };
+            let def = &self.defs[unit];
+            if def.starts_with('!') {
+                lines.push(format!("{unit} is primitive"));
+                break;
+            }
+            lines.push(format!("{unit} = {def}"));
+            // Follow a definition that's a single unit, perhaps times a number.
+            current = match unitparse::parse(def) {
+                Ok(Expr::Name(n)) => n,
+                Ok(Expr::Mul(a, b)) => match (*a, *b) {
+                    (Expr::Num(_), Expr::Name(n)) => n,
+                    _ => break,
+                },
+                _ => break,
+            };
+        }
+        lines.push(format!("= {}", self.parse(name)?));
+        Ok(lines)
+    }
+
+    /// Looks up a unit, following its definition down to primitive units.
+    fn lookup(&self, name: &str, depth: usize) -> Result<Reduced, String> {
+        if depth > MAX_LOOKUP_DEPTH {
+            return Err(format!("{name} is defined in terms of itself"));
+        }
+        self.find(name, true, depth).unwrap_or_else(|| {
+            if self.is_function(name) {
+                Err(format!("{name} is a function, used as in {name}(x)"))
             } else {
-                UnitExpr::Div(
-                    def.split(" ")
-                        .flat_map(|x| {
-                            if x.contains("^") {
-                                let mut s = x.split("^");
-                                let b = s.next().unwrap();
-                                let n = usize::from_str(s.next().unwrap()).unwrap();
-                                let mut v = vec![];
-                                for _i in 0..n {
-                                    v.push(b);
-                                }
-                                v
-                            } else {
-                                vec![x]
-                            }
-                        })
-                        .flat_map(|x| {
-                            if units.contains_key(x) {
-                                match &units[x] {
-                                    UnitExpr::Basic(b) => vec![b.clone()],
-                                    UnitExpr::Div(n, _d) => n.clone(), // Need to handle the denom
-                                    UnitExpr::Coef(c, e) => {
-                                        coef = coef * *c;
-                                        if let UnitExpr::Basic(b) = (**e).clone() {
-                                            vec![b.clone()]
-                                        } else if let UnitExpr::Div(n, _d) = (**e).clone() {
-                                            n.clone() // Need to handle the denom
-                                        } else {
-                                            vec![]
-                                        }
-                                    }
-                                }
-                            } else {
-                                println!("Couldn't find {x}");
-                                vec![]
-                            }
-                        })
-                        .collect(),
-                    vec![],
-                )
+                Err(format!("unknown unit {name}"))
+            }
+        })
+    }
+
+    /// Looks up a unit as it's spelled in the definitions, then follows
+    /// those.
+    fn find(&self, name: &str, prefixes: bool, depth: usize) -> Option<Result<Reduced, String>> {
+        let (prefix, unit) = self.resolve(name, prefixes)?;
+        let unit = match unit {
+            Some(u) => self.expand(u, &self.defs[u], depth),
+            None => Ok(Reduced::number(Coef::unit())),
+        };
+        Some(match prefix {
+            Some(p) => self
+                .expand(p, &self.prefixes[p], depth)
+                .and_then(|p| p * unit?),
+            None => unit,
+        })
+    }
+
+    /// How a name is spelled in the definitions, found as GNU units does: as
+    /// it's written; else as a plural, so `meters` is `meter` and `inches` is
+    /// `inch`; else with the longest prefix that leaves a unit, so `km` is
+    /// `k` and `m`. A prefix on its own is a number, and units can't have two
+    /// prefixes. Gives the prefix, if any, and the unit, if any, or `None`
+    /// if `name` isn't a unit at all.
+    fn resolve(&self, name: &str, prefixes: bool) -> Option<(Option<&str>, Option<&str>)> {
+        if let Some((unit, _)) = self.defs.get_key_value(name) {
+            return Some((None, Some(unit)));
+        }
+        if name.chars().count() > 2 {
+            let singulars = [
+                name.strip_suffix('s').map(str::to_owned),
+                name.strip_suffix("es").map(str::to_owned),
+                name.strip_suffix("ies").map(|n| format!("{n}y")),
+            ];
+            for singular in singulars.iter().flatten() {
+                if let Some(r) = self.resolve(singular, prefixes) {
+                    return Some(r);
+                }
+            }
+        }
+        if !prefixes {
+            return None;
+        }
+        let mut candidates: Vec<&String> = self
+            .prefixes
+            .keys()
+            .filter(|p| name.starts_with(p.as_str()))
+            .collect();
+        candidates.sort_by_key(|p| std::cmp::Reverse(p.len()));
+        candidates.into_iter().find_map(|prefix| {
+            let rest = &name[prefix.len()..];
+            let unit = if rest.is_empty() {
+                None
+            } else {
+                self.resolve(rest, false)?.1
             };
-            if let Some(nn) = c.name("num") {
-                let nr = usize::from_str(nn.into());
-                if let Ok(n) = nr {
-                    let d =
-                        usize::from_str(c.name("den").map(|c| c.into()).unwrap_or("1")).unwrap();
-                    coef = coef * Coef::Rational(Rational::new(n, d));
+            Some((Some(prefix.as_str()), unit))
+        })
+    }
+
+    /// Evaluates the definition `def` of the unit or prefix `name`.
+    fn expand(&self, name: &str, def: &str, depth: usize) -> Result<Reduced, String> {
+        match def {
+            // Dimensionless primitives such as radians are just numbers.
+            "!dimensionless" => Ok(Reduced::number(Coef::unit())),
+            def if def.starts_with('!') => Ok(Reduced {
+                coef: Coef::unit(),
+                dim: Dimension::primitive(name),
+            }),
+            def => {
+                let e = unitparse::parse(def)
+                    .map_err(|e| format!("in the definition of {name}: {e}"))?;
+                self.eval(&e, depth + 1, None)
+            }
+        }
+    }
+
+    /// Evaluates `e`, with the name in `bound`, if any, standing for a value,
+    /// as a function's parameter does in its definition.
+    fn eval(&self, e: &Expr, depth: usize, bound: Binding) -> Result<Reduced, String> {
+        let eval = |e: &Expr| self.eval(e, depth, bound);
+        match e {
+            Expr::Num(c) => Ok(Reduced::number(*c)),
+            Expr::Name(n) => match bound {
+                Some((name, value)) if name == n => Ok(value.clone()),
+                _ => self.lookup(n, depth),
+            },
+            Expr::Call(f, arg) => {
+                let x = eval(arg)?;
+                if let Some(func) = self.functions.get(f) {
+                    self.forward(f, func, x, depth)
+                } else if let Some(table) = self.tables.get(f) {
+                    self.table_forward(f, table, x, depth)
                 } else {
-                    let n = Coef::Float(f64::from_str(nn.into()).unwrap());
-                    coef = coef * n;
+                    call(f, x)
                 }
             }
-            if !coef.is_unit() {
-                e = UnitExpr::Coef(coef, Box::new(e));
+            Expr::Neg(x) => {
+                let x = eval(x)?;
+                Ok(Reduced {
+                    coef: -x.coef,
+                    dim: x.dim,
+                })
+            }
+            Expr::Add(a, b) => eval(a)?.add(eval(b)?),
+            Expr::Sub(a, b) => {
+                let b = eval(b)?;
+                let b = Reduced {
+                    coef: -b.coef,
+                    dim: b.dim,
+                };
+                eval(a)?.add(b)
+            }
+            Expr::Mul(a, b) => eval(a)? * eval(b)?,
+            Expr::Div(a, b) => eval(a)? / eval(b)?,
+            Expr::Pow(a, b) => {
+                let p = eval(b)?;
+                let p = p
+                    .as_number()
+                    .ok_or(format!("the power {p} isn't a number"))?;
+                eval(a)?.pow(p)
+            }
+        }
+    }
+
+    /// Evaluates a function's definition or inverse, written as `text`, with
+    /// `name` standing for `value`.
+    fn eval_bound(
+        &self,
+        text: &str,
+        name: &str,
+        value: &Reduced,
+        depth: usize,
+    ) -> Result<Reduced, String> {
+        if depth > MAX_LOOKUP_DEPTH {
+            return Err(format!("{name} is defined in terms of itself"));
+        }
+        let e = unitparse::parse(text).map_err(|e| format!("in the definition of {name}: {e}"))?;
+        self.eval(&e, depth + 1, Some((name, value)))
+    }
+
+    /// `x` as a number of `unit`, which it must conform to, or of primitive
+    /// units for a function that doesn't give its units.
+    fn in_units(&self, x: &Reduced, unit: Option<&str>, depth: usize) -> Result<f64, String> {
+        let unit = match unit {
+            Some(u) => self.eval(&unitparse::parse(u)?, depth + 1, None)?,
+            None => return Ok(x.coef.as_f64()),
+        };
+        if x.dim != unit.dim {
+            return Err(format!("expected {}, not {}", unit.dim, x.dim));
+        }
+        Ok((x.coef / unit.coef).as_f64())
+    }
+
+    /// A function applied to `x`, such as `tempF(75)`.
+    fn forward(
+        &self,
+        name: &str,
+        f: &Function,
+        x: Reduced,
+        depth: usize,
+    ) -> Result<Reduced, String> {
+        let (input, output) = f
+            .units
+            .as_ref()
+            .map(|(i, o)| (i.as_str(), o.as_str()))
+            .unzip();
+        let n = self
+            .in_units(&x, input, depth)
+            .map_err(|e| format!("{name}: {e}"))?;
+        if f.domain.is_some_and(|d| !d.contains(n)) {
+            return Err(format!("{n} is outside the domain of {name}"));
+        }
+        let y = self.eval_bound(&f.forward, &f.param, &x, depth)?;
+        self.in_units(&y, output, depth)
+            .map_err(|e| format!("{name} gives {}: {e}", y.dim))?;
+        Ok(y)
+    }
+
+    /// The argument a function gives `y` for, so `tempC` of 300 K is 26.85.
+    fn inverse(
+        &self,
+        name: &str,
+        f: &Function,
+        y: &Reduced,
+        depth: usize,
+    ) -> Result<Reduced, String> {
+        let (input, output) = f
+            .units
+            .as_ref()
+            .map(|(i, o)| (i.as_str(), o.as_str()))
+            .unzip();
+        let inverse = f.inverse.as_ref().ok_or(format!("{name} has no inverse"))?;
+        let n = self
+            .in_units(y, output, depth)
+            .map_err(|e| format!("{name}: {e}"))?;
+        if f.range.is_some_and(|r| !r.contains(n)) {
+            return Err(format!("{n} is outside the range of {name}"));
+        }
+        let x = self.eval_bound(inverse, name, y, depth)?;
+        self.in_units(&x, input, depth)
+            .map_err(|e| format!("the inverse of {name}: {e}"))?;
+        Ok(x)
+    }
+
+    /// A table interpolated at `x`, which must be a number.
+    fn table_forward(
+        &self,
+        name: &str,
+        t: &Table,
+        x: Reduced,
+        depth: usize,
+    ) -> Result<Reduced, String> {
+        let x = x
+            .as_number()
+            .ok_or(format!("{name} needs a number, not {x}"))?
+            .as_f64();
+        let y = interpolate(&t.points, x).ok_or(format!("{x} is outside the table {name}"))?;
+        Reduced::number(Coef::Float(y)) * self.eval(&unitparse::parse(&t.unit)?, depth + 1, None)?
+    }
+
+    /// The number a table gives `y` for.
+    fn table_inverse(
+        &self,
+        name: &str,
+        t: &Table,
+        y: &Reduced,
+        depth: usize,
+    ) -> Result<f64, String> {
+        let y = self
+            .in_units(y, Some(&t.unit), depth)
+            .map_err(|e| format!("{name}: {e}"))?;
+        let flipped: Vec<(f64, f64)> = t.points.iter().map(|(x, y)| (*y, *x)).collect();
+        interpolate(&flipped, y).ok_or(format!("{y} is outside the table {name}"))
+    }
+
+    /// Whether `name` is a function or table unit, such as `tempC`.
+    pub fn is_function(&self, name: &str) -> bool {
+        self.functions.contains_key(name) || self.tables.contains_key(name)
+    }
+
+    /// What a function or table unit gives its results in, if it says.
+    pub fn function_output(&self, name: &str) -> Option<&str> {
+        match self.functions.get(name) {
+            Some(f) => f.units.as_ref().map(|(_, o)| o.as_str()),
+            None => self.tables.get(name).map(|t| t.unit.as_str()),
+        }
+    }
+
+    /// A function or table unit applied to a plain number, as in `tempF(x)`.
+    pub fn apply(&self, name: &str, x: f64) -> Result<Reduced, String> {
+        let x = Reduced::number(Coef::Float(x));
+        match (self.functions.get(name), self.tables.get(name)) {
+            (Some(f), _) => self.forward(name, f, x, 0),
+            (None, Some(t)) => self.table_forward(name, t, x, 0),
+            (None, None) => Err(format!("unknown function {name}")),
+        }
+    }
+
+    /// The number a function or table unit gives `y` for, in its input units
+    /// if it has them, so `tempC` of `300 K` is 26.85.
+    pub fn invert(&self, name: &str, y: &Reduced) -> Result<f64, String> {
+        match (self.functions.get(name), self.tables.get(name)) {
+            (Some(f), _) => {
+                let x = self.inverse(name, f, y, 0)?;
+                let input = f.units.as_ref().map(|(i, _)| i.as_str());
+                self.in_units(&x, input, 0)
+            }
+            (None, Some(t)) => self.table_inverse(name, t, y, 0),
+            (None, None) => Err(format!("unknown function {name}")),
+        }
+    }
+}
+
+/// The `y` for `x` on straight lines between `points`, which run one way in
+/// `x`, or `None` outside them.
+fn interpolate(points: &[(f64, f64)], x: f64) -> Option<f64> {
+    points.windows(2).find_map(|w| {
+        let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+        if (x0 <= x && x <= x1) || (x1 <= x && x <= x0) {
+            Some(if x0 == x1 {
+                y0
+            } else {
+                y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// A unit expression in terms of the units named in it, which may only be
+/// multiplied, divided and raised to whole powers.
+fn named(e: &Expr) -> Result<UnitExpr, String> {
+    let whole = |e: &Expr| match e {
+        Expr::Num(Coef::Rational(r)) if r.is_integer() => Some(r.numerator()),
+        Expr::Neg(n) => match &**n {
+            Expr::Num(Coef::Rational(r)) if r.is_integer() => Some(-r.numerator()),
+            _ => None,
+        },
+        _ => None,
+    };
+    match e {
+        Expr::Num(c) => Ok(UnitExpr::number(*c)),
+        Expr::Name(n) => Ok(UnitExpr::basic(n)),
+        Expr::Mul(a, b) => Ok(named(a)? * named(b)?),
+        Expr::Div(a, b) => Ok(named(a)? / named(b)?),
+        Expr::Pow(a, b) => match whole(b) {
+            Some(p) => Ok(named(a)?.powi(p)),
+            None => Err("a quantity's units need whole powers".to_owned()),
+        },
+        _ => Err(
+            "a quantity's units can only be multiplied, divided and raised to powers".to_owned(),
+        ),
+    }
+}
+
+/// Applies one of the built-in functions, such as `sqrt` or `tan`.
+fn call(f: &str, x: Reduced) -> Result<Reduced, String> {
+    match f {
+        "sqrt" => return x.pow(Coef::Rational(Rational::new(1, 2))),
+        "cuberoot" => return x.pow(Coef::Rational(Rational::new(1, 3))),
+        _ => {}
+    }
+    let g: fn(f64) -> f64 = match f {
+        "exp" => f64::exp,
+        "ln" => f64::ln,
+        "log" => f64::log10,
+        "log2" => f64::log2,
+        "sin" => f64::sin,
+        "cos" => f64::cos,
+        "tan" => f64::tan,
+        "asin" => f64::asin,
+        "acos" => f64::acos,
+        "atan" => f64::atan,
+        "sinh" => f64::sinh,
+        "cosh" => f64::cosh,
+        "tanh" => f64::tanh,
+        "abs" => f64::abs,
+        "floor" => f64::floor,
+        "ceil" => f64::ceil,
+        "round" => f64::round,
+        _ => return Err(format!("unknown function {f}")),
+    };
+    let n = x
+        .as_number()
+        .ok_or(format!("{f} needs a number, not {x}"))?;
+    Ok(Reduced::number(Coef::Float(g(n.as_f64()))))
+}
+
+impl Function {
+    /// Parses what follows `name(param)`: any of `units=[in;out]`,
+    /// `domain=...`, `range=...` and `noerror`, then `forward ; inverse`.
+    fn parse(param: &str, def: &str) -> Option<Function> {
+        let mut f = Function {
+            param: param.trim().to_owned(),
+            units: None,
+            domain: None,
+            range: None,
+            forward: String::new(),
+            inverse: None,
+        };
+        let mut rest = def.trim_start();
+        loop {
+            if let Some(r) = rest.strip_prefix("units=[") {
+                let (units, r) = r.split_once(']')?;
+                let (input, output) = units.split_once(';')?;
+                f.units = Some((input.trim().to_owned(), output.trim().to_owned()));
+                rest = r.trim_start();
+            } else if let Some(r) = rest.strip_prefix("domain=") {
+                let (bounds, r) = r.split_once(char::is_whitespace)?;
+                f.domain = Some(Bounds::parse(bounds)?);
+                rest = r.trim_start();
+            } else if let Some(r) = rest.strip_prefix("range=") {
+                let (bounds, r) = r.split_once(char::is_whitespace)?;
+                f.range = Some(Bounds::parse(bounds)?);
+                rest = r.trim_start();
+            } else if let Some(r) = rest.strip_prefix("noerror") {
+                rest = r.trim_start();
+            } else {
+                break;
             }
-            //println!("{name:<20}=> {e:?}");
-            units.insert(name, e);
         }
+        let (forward, inverse) = match rest.split_once(';') {
+            Some((fwd, inv)) => (fwd, Some(inv.trim().to_owned())),
+            None => (rest, None),
+        };
+        f.forward = forward.trim().to_owned();
+        f.inverse = inverse.filter(|i| !i.is_empty());
+        if f.forward.is_empty() {
+            return None;
+        }
+        Some(f)
+    }
+}
+
+impl Table {
+    /// Parses the `x y` pairs following `name[unit]`, optionally separated
+    /// by commas.
+    fn parse(unit: &str, def: &str) -> Option<Table> {
+        let def = def.trim_start();
+        let def = def.strip_prefix("noerror").unwrap_or(def);
+        let numbers = def
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|n| !n.is_empty())
+            .map(f64::from_str)
+            .collect::<Result<Vec<f64>, _>>()
+            .ok()?;
+        if numbers.len() < 4 || numbers.len() % 2 != 0 {
+            return None;
+        }
+        Some(Table {
+            unit: unit.trim().to_owned(),
+            points: numbers.chunks(2).map(|p| (p[0], p[1])).collect(),
+        })
+    }
+}
+
+/// `$JSKCALC_UNITS_DIR`, or `~/.jskcalc.d` if that isn't set.
+fn user_dir() -> Option<PathBuf> {
+    if let Some(p) = std::env::var_os("JSKCALC_UNITS_DIR") {
+        return Some(PathBuf::from(p));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".jskcalc.d"))
+}
+
+/// Reads every `.units` file in `dir`, in order of name, so their
+/// definitions add to or replace the standard ones.
+fn read_user_files(units: &mut Units, dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "units"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        match fs::read_to_string(&path) {
+            Ok(text) => units.read(&text, Some(dir), 0),
+            Err(e) => println!("couldn't read units from {}: {e}", path.display()),
+        }
+    }
+}
+
+/// Loads the unit definitions from `$JSKCALC_UNITS`, else GNU units' own
+/// file if it's installed, else the copy built in, then the user's own.
+pub fn load_units() -> Units {
+    let mut units = Units::default();
+    match definitions_path() {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(text) => units.read(&text, path.parent(), 0),
+            Err(e) => {
+                println!("couldn't read units from {}: {e}", path.display());
+                units.read(BUILTIN, None, 0);
+            }
+        },
+        None => units.read(BUILTIN, None, 0),
+    }
+    if let Some(dir) = user_dir() {
+        read_user_files(&mut units, &dir);
     }
     units
 }
 
+/// The built-in definitions, whatever is installed.
+#[cfg(test)]
+pub fn builtin_units() -> Units {
+    let mut units = Units::default();
+    units.read(BUILTIN, None, 0);
+    units
+}
+
 #[cfg(test)]
 mod units_test {
     use super::*;
     #[test]
     pub fn expr() {
-        let m = UnitExpr::Coef(
-            Coef::Float(10.0),
-            Box::new(UnitExpr::Basic(Basic("m".to_owned()))),
+        let m = UnitExpr::basic("m");
+        let s = UnitExpr::basic("s");
+        let v = UnitExpr::number(Coef::Float(10.0)) * m.clone() / s.clone();
+        assert_eq!(v.to_string(), "10 m/s");
+        let a = m.clone() * m.clone();
+        assert_eq!(a.to_string(), "m^2");
+        assert_eq!((a.clone() / v.clone()).to_string(), "0.1 m s");
+        assert_eq!((v.clone() / a.clone()).to_string(), "10 1/m s");
+        // Sorted, so the order they're multiplied in doesn't matter.
+        assert_eq!(s.clone() * m.clone(), m.clone() * s.clone());
+        assert_eq!((m.powi(3) / s.clone()).to_string(), "m^3/s");
+        assert_eq!(m.clone() / m.clone(), UnitExpr::one());
+        assert_eq!(
+            UnitExpr::number(Coef::Rational(Rational::new(1, 12))) * UnitExpr::basic("ft"),
+            UnitExpr::basic("ft") / UnitExpr::number(Coef::Rational(Rational::from_int(12)))
+        );
+        assert_eq!(
+            (UnitExpr::number(Coef::Rational(Rational::new(1, 3))) * s.powi(-2)).to_string(),
+            "1|3 1/s^2"
         );
-        let s = UnitExpr::Basic(Basic("s".to_owned()));
-        let v = m.clone() / s.clone();
-        println!("{m} /  {s} = {v}");
+    }
+    #[test]
+    pub fn definitions_file() {
+        let mut units = Units::default();
+        units.read(
+            "m  !  # length
+             kilo-  1e3
+             k-     kilo
+             inch   2.54 \\
+                    cm
+             +inch  0.0254 m
+             tempF(x) units=[1;K] domain=[-459.67,) range=[0,) \\
+                    (x+(-32)) degF + stdtemp ; (tempF+(-stdtemp))/degF + 32
+             gauge[in] 0 0.3, 1 0.25 2 0.2
+             !set UNITS_ENGLISH US
+             !var UNITS_ENGLISH US
+             gallon usgallon
+             !endvar
+             !varnot UNITS_ENGLISH US
+             gallon brgallon
+             !endvar
+             !locale xx_XX
+             m 2 ft
+             !endlocale
+             !unitlist ftin ft;in
+             !include nonexistent.units
+             !message hello",
+            None,
+            0,
+        );
+        assert_eq!(units.defs["m"], "!");
+        assert_eq!(units.defs["inch"], "0.0254 m");
+        assert_eq!(units.prefixes["kilo"], "1e3");
+        assert_eq!(units.prefixes["k"], "kilo");
+        let f = &units.functions["tempF"];
+        assert_eq!(f.param, "x");
+        assert_eq!(f.units, Some(("1".to_owned(), "K".to_owned())));
+        assert_eq!(
+            f.domain,
+            Some(Bounds {
+                lo: Some((-459.67, true)),
+                hi: None
+            })
+        );
+        assert_eq!(f.forward, "(x+(-32)) degF + stdtemp");
+        assert_eq!(f.inverse.as_deref(), Some("(tempF+(-stdtemp))/degF + 32"));
+        assert_eq!(units.tables["gauge"].points.len(), 3);
+        assert_eq!(units.tables["gauge"].unit, "in");
+        if std::env::var_os("UNITS_ENGLISH").is_none() {
+            assert_eq!(units.defs["gallon"], "usgallon");
+        }
+        assert_eq!(units.unitlists["ftin"], "ft;in");
+        // The built-in copy has what `conv` needs.
+        let builtin = builtin_units();
+        for name in ["m", "s", "kg", "ft", "gallon", "N", "tempC"] {
+            assert!(
+                builtin.defs.contains_key(name) || builtin.functions.contains_key(name),
+                "{name}"
+            );
+        }
+    }
+    #[test]
+    pub fn expressions() {
+        let units = builtin_units();
+        let ratio = |a: &str, b: &str| units.convert(a, b).ok().map(Coef::as_f64);
+        assert_eq!(
+            units.convert("ft", "m"),
+            Ok(Coef::Rational(Rational::new(381, 1250)))
+        );
+        assert_eq!(
+            units.convert("ustbsp", "ustsp"),
+            Ok(Coef::Rational(Rational::from_int(3)))
+        );
+        assert_eq!(ratio("N", "kg*m/s^2"), Some(1.0));
+        assert_eq!(ratio("N", "kg m s^-2"), Some(1.0));
+        assert_eq!(ratio("J", "kg m^2 per s^2"), Some(1.0));
+        assert_eq!(ratio("1|12 ft", "inch"), Some(1.0));
+        assert_eq!(ratio("acre", "43560 ft^2"), Some(1.0));
+        assert_eq!(ratio("(m^2)^0.5", "m"), Some(1.0));
+        assert!((ratio("sqrt(acre)", "66 sqrt(10) ft").unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(ratio("m/s s", "m/s^2"), Some(1.0));
+        assert!((ratio("parsec", "ly").unwrap() - 3.2616).abs() < 1e-4);
+        assert_eq!(
+            units.parse("frobnitzes"),
+            Err("unknown unit frobnitzes".to_owned())
+        );
+        assert!(units.parse("m + s").is_err());
+    }
+    #[test]
+    pub fn prefixes() {
+        let units = builtin_units();
+        let ratio = |a: &str, b: &str| units.convert(a, b).ok().map(Coef::as_f64);
+        assert_eq!(ratio("km", "m"), Some(1000.0));
+        assert_eq!(ratio("mL", "cc"), Some(1.0));
+        assert_eq!(ratio("µs", "s"), Some(1e-6));
+        assert_eq!(ratio("MiB", "byte"), Some(1048576.0));
+        assert_eq!(ratio("kilowatthour", "J"), Some(3.6e6));
+        assert_eq!(ratio("kWh", "J"), Some(3.6e6));
+        assert_eq!(ratio("Qm", "m"), Some(1e30));
+        assert_eq!(ratio("quectogram", "kg"), Some(1e-33));
+        assert_eq!(ratio("kilo", "1"), Some(1000.0));
+        assert_eq!(ratio("dam", "m"), Some(10.0));
+        // Longest prefix first, so `mm` is millimeters and `min` minutes.
+        assert_eq!(ratio("mm", "m"), Some(0.001));
+        assert_eq!(ratio("min", "s"), Some(60.0));
+        assert_eq!(ratio("meters", "m"), Some(1.0));
+        assert_eq!(ratio("inches", "inch"), Some(1.0));
+        assert_eq!(ratio("kilometers", "km"), Some(1.0));
+        assert_eq!(ratio("ms", "s"), Some(0.001));
+        assert_eq!(ratio("centuries", "century"), Some(1.0));
+        // Only one prefix.
+        assert!(units.parse("kkm").is_err());
+        assert!(units.parse("kilofrobnitz").is_err());
+        // Prefixes can be defined like units.
+        let mut units = builtin_units();
+        units.read("bogo- 1e-2", None, 0);
+        assert_eq!(units.convert("bogom", "cm"), Ok(Coef::unit()));
+    }
+    #[test]
+    pub fn functions() {
+        let units = builtin_units();
+        let conv = |a: &str, b: &str| units.convert(a, b).map(Coef::as_f64);
+        let close = |r: Result<f64, String>, x: f64| (r.unwrap() - x).abs() < 1e-9;
+        assert!(close(conv("tempF(212)", "tempC"), 100.0));
+        assert!(close(conv("tempC(-40)", "tempF"), -40.0));
+        assert!(close(conv("tempC(0)", "K"), 273.15));
+        assert!(close(conv("300 K", "tempC"), 26.85));
+        // A degree is a difference, not a temperature.
+        assert!(close(conv("degF", "degC"), 5.0 / 9.0));
+        assert!(close(conv("10 degC", "K"), 10.0));
+        assert!(close(conv("dBm(30)", "W"), 1.0));
+        assert!(close(conv("1 W", "dBm"), 30.0));
+        assert!(close(conv("pH(7)", "mol/liter"), 1e-7));
+        assert!(close(conv("awg(12)", "mm"), 2.052525388493948));
+        assert!(close(conv("2.052525388493948 mm", "awg"), 12.0));
+        assert!(close(conv("brwiregauge(9.5)", "in"), 0.136));
+        assert!(close(conv("0.136 in", "brwiregauge"), 9.5));
+        assert!(close(conv("shoesize_men(10)", "inch"), 11.0 + 7.0 / 12.0));
+        assert_eq!(
+            conv("tempC(-300)", "K"),
+            Err("-300 is outside the domain of tempC".to_owned())
+        );
+        assert_eq!(
+            conv("tempF", "K"),
+            Err("tempF is a function, used as in tempF(x)".to_owned())
+        );
+        assert_eq!(
+            conv("ustsp", "tempC"),
+            Err("tempC: expected K, not m^3".to_owned())
+        );
+        assert!(conv("brwiregauge(30)", "in").is_err());
+        let k = units.apply("tempF", 32.0).unwrap();
+        assert!(close(units.invert("tempC", &k), 0.0));
+    }
+    #[test]
+    pub fn express() {
+        let units = builtin_units();
+        let express = |u: &str, style: &UnitStyle| {
+            let dim = units.parse(u).unwrap().dim;
+            units.express(&dim, style).map(|(u, _)| u.to_string())
+        };
+        assert_eq!(express("ft lb/s^2", &UnitStyle::Keep), None);
+        assert_eq!(
+            express("ft lb/s^2", &UnitStyle::Base),
+            Some("kg m/s^2".to_owned())
+        );
+        assert_eq!(express("ft lb/s^2", &UnitStyle::Si), Some("N".to_owned()));
+        assert_eq!(express("N m", &UnitStyle::Si), Some("J".to_owned()));
+        assert_eq!(express("kg m/s", &UnitStyle::Si), Some("kg m/s".to_owned()));
+        let us = UnitStyle::Prefer(vec!["lbf".to_owned(), "ft".to_owned(), "lb".to_owned()]);
+        assert_eq!(express("N", &us), Some("lbf".to_owned()));
+        assert_eq!(express("m^3/s", &us), Some("ft^3/s".to_owned()));
+        assert_eq!(express("kg/m^3", &us), Some("lb/ft^3".to_owned()));
+        assert_eq!(express("percent", &UnitStyle::Si), None);
+        assert_eq!(express("m^0.5", &UnitStyle::Base), None);
+        let (unit, reduced) = units
+            .express(&units.parse("lbf").unwrap().dim, &us)
+            .unwrap();
+        assert_eq!(reduced, units.parse("lbf").unwrap());
+        assert_eq!(unit, UnitExpr::basic("lbf"));
+    }
+    #[test]
+    pub fn dimensions() {
+        let units = builtin_units();
+        let dim = |u: &str| units.parse(u).unwrap().dim.to_string();
+        assert_eq!(dim("N"), "kg m/s^2");
+        assert_eq!(dim("Hz"), "1/s");
+        assert_eq!(dim("m^0.5"), "m^1|2");
+        assert_eq!(dim("sqrt(m) sqrt(m)"), "m");
+        assert_eq!(dim("radian"), "1");
+        assert_eq!(units.dimension("ustsp"), Ok("volume (m^3)".to_owned()));
+        assert_eq!(units.dimension("btu"), Ok("energy (kg m^2/s^2)".to_owned()));
+        assert_eq!(units.dimension("m^2/s"), Ok("m^2/s".to_owned()));
+        assert_eq!(
+            units.dimension("percent"),
+            Ok("dimensionless (1)".to_owned())
+        );
+        assert_eq!(
+            units.convert("ustsp", "ft"),
+            Err("incompatible dimensions: volume vs length\n  ustsp is m^3\n  ft is m".to_owned())
+        );
+        assert!(units.convert("mile", "hour").is_err());
+    }
+    #[test]
+    pub fn discovery() {
+        let units = builtin_units();
+        assert!(units.search("GAL").contains(&"gallon"));
+        assert!(units.search("gln").contains(&"gallon"));
+        let volume = units.dimension_of("volume").unwrap();
+        assert_eq!(volume, units.parse("m^3").unwrap().dim);
+        assert!(units.conformable(&volume).contains(&"ustsp"));
+        assert!(!units.conformable(&volume).contains(&"ft"));
+        assert!(units
+            .conformable(&units.dimension_of("K").unwrap())
+            .contains(&"tempF"));
+        assert!(units.dimension_of("frobnitz").is_err());
+        let def = units.definition("km").unwrap();
+        assert_eq!(def, ["km = k- m", "m is primitive", "= 1000 m"]);
+        let def = units.definition("ustbsp").unwrap();
+        assert_eq!(def[0], "ustbsp = ustablespoon");
+        assert!(def.last().unwrap().ends_with(" m^3"));
+        assert!(units.definition("tempF").unwrap()[0].starts_with("tempF(x) = "));
+    }
+    #[test]
+    pub fn user_definitions() {
+        let mut units = builtin_units();
+        assert_eq!(units.define_unit("widget", "!"), Ok(()));
+        assert_eq!(units.define_unit("pallet", "48 widget"), Ok(()));
+        assert_eq!(units.define_unit("truckload", "20 pallets"), Ok(()));
+        assert_eq!(
+            units.convert("truckload", "widget").unwrap().as_f64(),
+            960.0
+        );
+        assert!(units.convert("pallet", "m").is_err());
+        assert_eq!(units.dimension("pallet"), Ok("widget".to_owned()));
+        // A bad definition leaves the old one alone.
+        assert!(units.define_unit("pallet", "3 frobnitz").is_err());
+        assert_eq!(units.convert("pallet", "widget").unwrap().as_f64(), 48.0);
+        assert!(units.define_unit("frobnitz", "3 frobnitz").is_err());
+        assert!(units.parse("frobnitz").is_err());
+        assert_eq!(units.define_unit("dozen-", "12"), Ok(()));
+        assert_eq!(
+            units.convert("dozenwidget", "widget").unwrap().as_f64(),
+            12.0
+        );
+        assert_eq!(
+            units.define_unit("half(x)", "units=[1;1] x/2 ; half*2"),
+            Ok(())
+        );
+        assert!(units.is_function("half"));
+        assert!(units.define_unit("nothing", "").is_err());
 
-        let m = UnitExpr::Basic(Basic("m".to_owned()));
-        let a = m.clone() * m.clone();
-        println!("{m} *  {m} = {a}");
-
-        let v2 = a.clone() / v.clone();
-        println!("{a} /  {v} = {v2}");
-
-        let v2 = v.clone() / a.clone();
-        println!("{v} /  {a} = {v2}");
-        let units = load_units();
-
-        let tsp = units["ustsp"].clone();
-        let tbsp = units["ustbsp"].clone();
-        println!("{tsp:?}");
-        println!("{tbsp:?}");
-        let conv = tbsp.clone() / tsp.clone();
-        println!("tbsp / tsp = {conv:?}");
-        let conv = tsp / tbsp;
-        println!("tsp / tbsp = {conv:?}");
+        let dir = std::env::temp_dir().join(format!("jskcalc-units-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.units"), "rackunit 2 in\n").unwrap();
+        fs::write(dir.join("a.units"), "rackunit 1 in\nRU rackunit\n").unwrap();
+        fs::write(dir.join("notes.txt"), "RU 5 in\n").unwrap();
+        read_user_files(&mut units, &dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(units.convert("RU", "in").unwrap().as_f64(), 2.0);
     }
 }